@@ -0,0 +1,139 @@
+//! Measures the rope backed `Buffer` on a generated file of several hundred
+//! megabytes. Run with `cargo bench`, and set `HECTO_BENCH_MB` to change the
+//! size of the generated file.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+#[path = "../src/editor/terminal.rs"]
+mod terminal;
+
+#[path = "../src/editor/view"]
+mod view {
+    pub mod buffer;
+    pub mod history;
+    pub mod search;
+}
+
+use terminal::Position;
+use view::buffer::Buffer;
+
+const DEFAULT_FILE_SIZE_MB: usize = 300;
+const OPERATIONS: usize = 10_000;
+
+/// Small linear congruential generator so the benchmark stays reproducible
+/// without pulling a random number crate.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((self.0 >> 33) as usize) % bound.max(1)
+    }
+}
+
+fn main() {
+    let file_size_mb = env::var("HECTO_BENCH_MB")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_FILE_SIZE_MB);
+    let file_path = env::temp_dir().join(format!("hecto_bench_{file_size_mb}mb.txt"));
+    env::set_var("XDG_DATA_HOME", env::temp_dir().join("hecto_bench_data"));
+
+    generate_file(&file_path, file_size_mb);
+
+    let (mut buffer, elapsed) = measure(|| Buffer::load(&file_path).expect("Couldn't load file"));
+    report("load", elapsed, 1);
+
+    let line_count = buffer.line_count();
+    println!("{file_size_mb} MB, {line_count} lines");
+
+    let (snapshots, elapsed) = measure(|| {
+        (0..OPERATIONS)
+            .map(|_| buffer.clone())
+            .collect::<Vec<Buffer>>()
+    });
+    report("snapshot", elapsed, OPERATIONS);
+    drop(snapshots);
+
+    let mut random = Lcg(42);
+    let (_, elapsed) = measure(|| {
+        for _ in 0..OPERATIONS {
+            let line_index = random.next(line_count);
+            std::hint::black_box(buffer.line(line_index));
+        }
+    });
+    report("line access", elapsed, OPERATIONS);
+
+    let (_, elapsed) = measure(|| {
+        for _ in 0..OPERATIONS {
+            let y = random.next(line_count);
+            let x = random.next(buffer.line_length(y));
+            buffer.insert_char('x', &Position { x, y });
+        }
+    });
+    report("insert char", elapsed, OPERATIONS);
+
+    let (_, elapsed) = measure(|| {
+        for _ in 0..OPERATIONS {
+            let y = random.next(line_count);
+            buffer.insert_newline(&Position { x: 0, y });
+        }
+    });
+    report("split line", elapsed, OPERATIONS);
+
+    let (_, elapsed) = measure(|| {
+        for _ in 0..OPERATIONS {
+            let y = random.next(buffer.line_count() - 1);
+            let x = buffer.line_length(y);
+            buffer.delete(&Position { x, y });
+        }
+    });
+    report("join lines", elapsed, OPERATIONS);
+
+    buffer.file_path = Some(file_path.with_extension("saved.txt"));
+    let (_, elapsed) = measure(|| buffer.save().expect("Couldn't save file"));
+    report("save", elapsed, 1);
+
+    let _ = fs::remove_file(&file_path);
+    let _ = fs::remove_dir_all(env::temp_dir().join("hecto_bench_data"));
+    if let Some(saved_path) = &buffer.file_path {
+        let _ = fs::remove_file(saved_path);
+    }
+}
+
+fn generate_file(file_path: &Path, file_size_mb: usize) {
+    let line = "The quick brown fox jumps over the lazy dog, then naps under a tree.\n";
+    let line_count = file_size_mb * 1024 * 1024 / line.len();
+    let mut writer =
+        BufWriter::new(fs::File::create(file_path).expect("Couldn't create benchmark file"));
+
+    for _ in 0..line_count {
+        writer
+            .write_all(line.as_bytes())
+            .expect("Couldn't write benchmark file");
+    }
+    writer.flush().expect("Couldn't write benchmark file");
+}
+
+fn measure<T>(operation: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = operation();
+    (result, start.elapsed())
+}
+
+fn report(name: &str, elapsed: Duration, operations: usize) {
+    println!(
+        "{name:<12} {:>10.3} ms total {:>10.3} µs/op",
+        elapsed.as_secs_f64() * 1000.0,
+        elapsed.as_secs_f64() * 1_000_000.0 / operations as f64
+    );
+}
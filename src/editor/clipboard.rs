@@ -0,0 +1,76 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use super::{terminal, user_configuration::ClipboardConfiguration};
+
+/// The system clipboard behind the `+` and `*` registers.
+pub struct Clipboard {
+    configuration: ClipboardConfiguration,
+}
+
+impl Clipboard {
+    pub fn new(configuration: ClipboardConfiguration) -> Self {
+        Self { configuration }
+    }
+
+    /// Copies through the terminal, falling back to the copy command when
+    /// OSC 52 is off or the text is too long for it.
+    pub fn copy(&self, text: &str) -> Result<(), String> {
+        let osc52_result = if self.configuration.osc52 {
+            terminal::copy_to_clipboard(text, self.configuration.osc52_limit)
+                .map_err(|err| format!("Couldn't copy to the clipboard: {err}"))
+        } else {
+            Err(String::from("No clipboard copy command set"))
+        };
+
+        match (osc52_result, &self.configuration.copy_command) {
+            (Ok(()), _) => Ok(()),
+            (Err(_), Some(copy_command)) => run(copy_command, Some(text)).map(|_| ()),
+            (Err(err), None) => Err(err),
+        }
+    }
+
+    /// Text printed by the paste command, or `None` without one.
+    pub fn paste(&self) -> Result<Option<String>, String> {
+        self.configuration
+            .paste_command
+            .as_deref()
+            .map(|paste_command| run(paste_command, None))
+            .transpose()
+    }
+}
+
+/// Runs a command split on whitespace, writing `input` to it and returning
+/// what it prints when there is no input. The output of copy commands isn't
+/// read, as some keep running to serve the clipboard.
+fn run(command_line: &str, input: Option<&str>) -> Result<String, String> {
+    let mut words = command_line.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| String::from("Empty clipboard command"))?;
+    let mut command = Command::new(program);
+    command.args(words).stderr(Stdio::null());
+    match input {
+        Some(_) => command.stdin(Stdio::piped()).stdout(Stdio::null()),
+        None => command.stdin(Stdio::null()).stdout(Stdio::piped()),
+    };
+
+    let mut child = command
+        .spawn()
+        .map_err(|err| format!("Couldn't run {program}: {err}"))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|err| format!("Couldn't write to {program}: {err}"))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Couldn't run {program}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("{program} failed: {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,585 @@
+use std::{fs, ops::Range};
+
+use super::{view::MAX_TAB_STOP, Editor};
+
+/// Whether a command takes an argument after its name.
+#[derive(PartialEq)]
+enum ArgumentKind {
+    None,
+    Optional,
+    Required,
+}
+
+type CommandHandler = fn(&mut Editor, &ParsedCommand) -> Result<(), String>;
+
+struct CommandDefinition {
+    name: &'static str,
+    /// Shortest prefix of `name` accepted as the command, like `w` for `write`.
+    abbreviation_length: usize,
+    argument: ArgumentKind,
+    allows_bang: bool,
+    allows_range: bool,
+    handler: CommandHandler,
+}
+
+const COMMANDS: &[CommandDefinition] = &[
+    CommandDefinition {
+        name: "write",
+        abbreviation_length: 1,
+        argument: ArgumentKind::Optional,
+        allows_bang: true,
+        allows_range: false,
+        handler: write,
+    },
+    CommandDefinition {
+        name: "wq",
+        abbreviation_length: 2,
+        argument: ArgumentKind::Optional,
+        allows_bang: true,
+        allows_range: false,
+        handler: write_quit,
+    },
+    CommandDefinition {
+        name: "quit",
+        abbreviation_length: 1,
+        argument: ArgumentKind::None,
+        allows_bang: true,
+        allows_range: false,
+        handler: quit,
+    },
+    CommandDefinition {
+        name: "saveas",
+        abbreviation_length: 3,
+        argument: ArgumentKind::Required,
+        allows_bang: true,
+        allows_range: false,
+        handler: save_as,
+    },
+    CommandDefinition {
+        name: "edit",
+        abbreviation_length: 1,
+        argument: ArgumentKind::Optional,
+        allows_bang: true,
+        allows_range: false,
+        handler: edit,
+    },
+    CommandDefinition {
+        name: "set",
+        abbreviation_length: 2,
+        argument: ArgumentKind::Optional,
+        allows_bang: false,
+        allows_range: false,
+        handler: set,
+    },
+    CommandDefinition {
+        name: "nohlsearch",
+        abbreviation_length: 3,
+        argument: ArgumentKind::None,
+        allows_bang: false,
+        allows_range: false,
+        handler: no_highlight_search,
+    },
+    CommandDefinition {
+        name: "substitute",
+        abbreviation_length: 1,
+        argument: ArgumentKind::Required,
+        allows_bang: false,
+        allows_range: true,
+        handler: substitute,
+    },
+    CommandDefinition {
+        name: "retab",
+        abbreviation_length: 3,
+        argument: ArgumentKind::Optional,
+        allows_bang: true,
+        allows_range: true,
+        handler: retab,
+    },
+    CommandDefinition {
+        name: "registers",
+        abbreviation_length: 3,
+        argument: ArgumentKind::Optional,
+        allows_bang: false,
+        allows_range: false,
+        handler: registers,
+    },
+    CommandDefinition {
+        name: "display",
+        abbreviation_length: 2,
+        argument: ArgumentKind::Optional,
+        allows_bang: false,
+        allows_range: false,
+        handler: registers,
+    },
+];
+
+/// A command line split into its parts, like `w! file.txt` or
+/// `%s/old/new/g`.
+pub struct ParsedCommand {
+    pub range: Option<LineRange>,
+    pub name: String,
+    pub bang: bool,
+    pub argument: Option<String>,
+}
+
+enum CommandLineInput {
+    LineJump(Address),
+    Command(ParsedCommand),
+}
+
+/// A line given before a command, like `5`, `.`, `$` or `'<`.
+#[derive(Clone, Copy)]
+enum Address {
+    Number(usize),
+    Current,
+    Last,
+    Mark(char),
+}
+
+/// Lines a command applies to, like `%` or `3,$`.
+pub struct LineRange {
+    start: Address,
+    end: Address,
+}
+
+impl Address {
+    /// Resolves the address into a line index of the buffer.
+    fn line_index(&self, editor: &Editor) -> Result<usize, String> {
+        let line_count = editor.view.buffer.line_count();
+
+        match self {
+            Address::Number(number) if *number > line_count => Err(String::from("Invalid range")),
+            Address::Number(number) => Ok(number.saturating_sub(1)),
+            Address::Current => Ok(editor.view.location.y),
+            Address::Last => Ok(line_count.saturating_sub(1)),
+            Address::Mark(mark) => editor
+                .view
+                .buffer
+                .mark(*mark)
+                .map(|at| std::cmp::min(at.y, line_count.saturating_sub(1)))
+                .ok_or_else(|| format!("Mark not set: '{mark}")),
+        }
+    }
+
+    fn parse(text: &str) -> Option<(Self, &str)> {
+        let digit_count = text.chars().take_while(char::is_ascii_digit).count();
+        if digit_count > 0 {
+            let number = text[..digit_count].parse().ok()?;
+            return Some((Address::Number(number), &text[digit_count..]));
+        }
+
+        let mut chars = text.chars();
+        let address = match chars.next()? {
+            '.' => Address::Current,
+            '$' => Address::Last,
+            '\'' => Address::Mark(chars.next()?),
+            _ => return None,
+        };
+        Some((address, chars.as_str()))
+    }
+}
+
+impl LineRange {
+    /// Resolves the range into line indices, failing when it's backwards.
+    fn line_indices(&self, editor: &Editor) -> Result<Range<usize>, String> {
+        let start = self.start.line_index(editor)?;
+        let end = self.end.line_index(editor)?;
+
+        if start > end {
+            return Err(String::from("Backwards range given"));
+        }
+        Ok(start..end + 1)
+    }
+
+    fn parse(text: &str) -> Result<(Option<Self>, &str), String> {
+        if let Some(rest) = text.strip_prefix('%') {
+            let range = LineRange {
+                start: Address::Number(1),
+                end: Address::Last,
+            };
+            return Ok((Some(range), rest));
+        }
+
+        let Some((start, rest)) = Address::parse(text) else {
+            return Ok((None, text));
+        };
+        let Some(rest) = rest.strip_prefix(',') else {
+            return Ok((Some(LineRange { start, end: start }), rest));
+        };
+        let (end, rest) = Address::parse(rest).ok_or_else(|| format!("Invalid range: {text}"))?;
+
+        Ok((Some(LineRange { start, end }), rest))
+    }
+}
+
+/// Parses and runs a command typed in command mode.
+pub fn execute(editor: &mut Editor, command_line: &str) -> Result<(), String> {
+    match parse(command_line)? {
+        CommandLineInput::LineJump(address) => {
+            let line_index = match address {
+                Address::Number(number) => number.saturating_sub(1),
+                address => address.line_index(editor)?,
+            };
+            editor.view.jump_to_line(line_index + 1);
+        }
+        CommandLineInput::Command(command) => {
+            let definition = find_command(&command.name)?;
+
+            if command.bang && !definition.allows_bang {
+                return Err(String::from("No ! allowed"));
+            }
+
+            if command.range.is_some() && !definition.allows_range {
+                return Err(String::from("No range allowed"));
+            }
+
+            match (&definition.argument, &command.argument) {
+                (ArgumentKind::None, Some(argument)) => {
+                    return Err(format!("Trailing characters: {argument}"));
+                }
+                (ArgumentKind::Required, None) => return Err(String::from("Argument required")),
+                _ => (),
+            }
+
+            (definition.handler)(editor, &command)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse(command_line: &str) -> Result<CommandLineInput, String> {
+    let command_line = trim_unescaped_end(command_line.trim_start_matches([':', ' ']));
+    let (range, command_line) = LineRange::parse(command_line)?;
+    let command_line = command_line.trim_start();
+
+    if command_line.is_empty() {
+        if let Some(range) = range {
+            return Ok(CommandLineInput::LineJump(range.end));
+        }
+    }
+
+    let name_length = command_line
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(command_line.len());
+    let (name, rest) = command_line.split_at(name_length);
+
+    if name.is_empty() {
+        return Err(format!("Not an editor command: {command_line}"));
+    }
+
+    let (bang, argument) = match rest.strip_prefix('!') {
+        Some(argument) => (true, argument.trim_start()),
+        None => (false, rest.trim_start()),
+    };
+
+    Ok(CommandLineInput::Command(ParsedCommand {
+        range,
+        name: name.to_string(),
+        bang,
+        argument: Some(argument.to_string()).filter(|argument| !argument.is_empty()),
+    }))
+}
+
+/// Trims the whitespace at the end of a command line, keeping a space
+/// escaped by a backslash like in `:set showbreak=>\ `.
+fn trim_unescaped_end(command_line: &str) -> &str {
+    let trimmed = command_line.trim_end();
+    let backslash_count = trimmed.chars().rev().take_while(|c| *c == '\\').count();
+
+    match command_line[trimmed.len()..].chars().next() {
+        Some(space) if backslash_count % 2 == 1 => {
+            &command_line[..trimmed.len() + space.len_utf8()]
+        }
+        _ => trimmed,
+    }
+}
+
+fn find_command(name: &str) -> Result<&'static CommandDefinition, String> {
+    COMMANDS
+        .iter()
+        .find(|definition| {
+            name.len() >= definition.abbreviation_length && definition.name.starts_with(name)
+        })
+        .ok_or_else(|| format!("Not an editor command: {name}"))
+}
+
+fn write(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    match &command.argument {
+        Some(file_path) => {
+            check_overwrite(editor, file_path, command.bang)?;
+            editor.write_to(file_path)?;
+        }
+        None => editor.save(),
+    }
+
+    Ok(())
+}
+
+fn write_quit(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    write(editor, command)?;
+    if command.argument.is_some() {
+        // Only a copy was written, so the buffer may still have changes.
+        editor.request_quit();
+    } else {
+        editor.should_quit = !editor.view.buffer.modified;
+    }
+    Ok(())
+}
+
+fn quit(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    if command.bang {
+        editor.should_quit = true;
+    } else {
+        editor.request_quit();
+    }
+
+    Ok(())
+}
+
+fn save_as(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    if let Some(file_path) = &command.argument {
+        check_overwrite(editor, file_path, command.bang)?;
+        editor.save_as(file_path);
+    }
+
+    Ok(())
+}
+
+/// Refuses to write over another file that exists unless `!` was given.
+fn check_overwrite(editor: &Editor, file_path: &str, bang: bool) -> Result<(), String> {
+    // Only a file that exists can be resolved.
+    let Ok(target_path) = fs::canonicalize(file_path) else {
+        return Ok(());
+    };
+    let is_own_file = editor
+        .view
+        .buffer
+        .file_path
+        .as_deref()
+        .and_then(|own_path| fs::canonicalize(own_path).ok())
+        .is_some_and(|own_path| own_path == target_path);
+
+    if bang || is_own_file {
+        Ok(())
+    } else {
+        Err(String::from("File exists (add ! to override)"))
+    }
+}
+
+fn edit(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    if editor.view.buffer.modified && !command.bang {
+        return Err(String::from(
+            "No write since last change (add ! to override)",
+        ));
+    }
+
+    let file_path = match &command.argument {
+        Some(file_path) => file_path.to_string(),
+        None => editor
+            .view
+            .buffer
+            .file_name()
+            .ok_or_else(|| String::from("No file name"))?,
+    };
+
+    editor
+        .view
+        .load_file(file_path.clone())
+        .map_err(|err| format!("Couldn't open {file_path}: {err}"))?;
+    editor.show_message(format!(
+        "\"{file_path}\" {}L",
+        editor.view.buffer.line_count()
+    ));
+    Ok(())
+}
+
+fn set(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    let Some(arguments) = &command.argument else {
+        let description = editor.view.settings.describe_all();
+        editor.show_message(description);
+        return Ok(());
+    };
+
+    let mut shown_values = Vec::new();
+    let mut result = Ok(());
+    for argument in split_set_arguments(arguments) {
+        match editor.view.settings.apply(&argument) {
+            Ok(Some(value)) => shown_values.push(value),
+            Ok(None) => (),
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+
+    // Options applied before a failing argument still take effect.
+    editor.view.update_layout();
+    if result.is_ok() && !shown_values.is_empty() {
+        editor.show_message(shown_values.join("  "));
+    }
+    result
+}
+
+/// Splits `:set` arguments on whitespace, where `\ ` puts a space into a
+/// value.
+fn split_set_arguments(arguments: &str) -> Vec<String> {
+    let mut split_arguments = Vec::new();
+    let mut argument = String::new();
+    let mut chars = arguments.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped.is_whitespace() => argument.push(escaped),
+                Some(other) => {
+                    argument.push('\\');
+                    argument.push(other);
+                }
+                None => argument.push('\\'),
+            },
+            _ if c.is_whitespace() => {
+                if !argument.is_empty() {
+                    split_arguments.push(std::mem::take(&mut argument));
+                }
+            }
+            _ => argument.push(c),
+        }
+    }
+
+    if !argument.is_empty() {
+        split_arguments.push(argument);
+    }
+    split_arguments
+}
+
+fn no_highlight_search(editor: &mut Editor, _command: &ParsedCommand) -> Result<(), String> {
+    editor.view.clear_search_highlight();
+    Ok(())
+}
+
+fn substitute(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    let Some(argument) = &command.argument else {
+        return Ok(());
+    };
+    let lines = match &command.range {
+        Some(range) => range.line_indices(editor)?,
+        None => editor.view.location.y..editor.view.location.y + 1,
+    };
+    let (pattern, replacement, flags) = parse_substitute_argument(argument)?;
+
+    let mut global = false;
+    let mut confirm = false;
+    let mut ignore_case = false;
+    for flag in flags.chars() {
+        match flag {
+            'g' => global = true,
+            'c' => confirm = true,
+            'i' => ignore_case = true,
+            _ => return Err(format!("Trailing characters: {flags}")),
+        }
+    }
+
+    editor
+        .view
+        .start_substitution(&pattern, &replacement, global, ignore_case, lines)?;
+
+    if confirm {
+        editor.change_to_substitute_confirmation();
+    } else {
+        editor.view.replace_remaining_matches();
+        editor.finish_substitution();
+    }
+
+    Ok(())
+}
+
+/// Splits `/pattern/replacement/flags` on its first char, where the
+/// delimiter can be escaped with a backslash and the trailing parts can be
+/// left out.
+fn parse_substitute_argument(argument: &str) -> Result<(String, String, String), String> {
+    let mut chars = argument.chars();
+    let delimiter = chars
+        .next()
+        .filter(|delimiter| !delimiter.is_alphanumeric() && !matches!(delimiter, '\\' | '"' | '|'))
+        .ok_or_else(|| String::from("Regular expressions can't be delimited by letters"))?;
+
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped == delimiter => part.push(escaped),
+                Some(escaped) => {
+                    part.push('\\');
+                    part.push(escaped);
+                }
+                None => part.push('\\'),
+            },
+            _ if c == delimiter && parts.len() < 2 => parts.push(std::mem::take(&mut part)),
+            _ => part.push(c),
+        }
+    }
+    parts.push(part);
+
+    let mut parts = parts.into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts.next().unwrap_or_default();
+    let flags = parts.next().unwrap_or_default();
+    Ok((pattern, replacement, flags.trim().to_string()))
+}
+
+/// Rewrites the tabs of the range, the whole buffer by default, for the
+/// given tab stop or the current one.
+fn retab(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    let lines = match &command.range {
+        Some(range) => range.line_indices(editor)?,
+        None => 0..editor.view.buffer.line_count(),
+    };
+    let tab_stop = match &command.argument {
+        Some(argument) => match argument.parse() {
+            Ok(0) => return Err(format!("Argument must be positive: {argument}")),
+            Ok(tab_stop) if tab_stop <= MAX_TAB_STOP => tab_stop,
+            Ok(_) => return Err(format!("Invalid argument: {argument}")),
+            Err(_) => return Err(format!("Invalid argument: {argument}")),
+        },
+        None => editor.view.settings.tab_stop,
+    };
+
+    editor.view.retab(lines, tab_stop, command.bang);
+    Ok(())
+}
+
+/// Shows what the registers hold, or only the ones named in the argument,
+/// with line breaks as `^J`.
+fn registers(editor: &mut Editor, command: &ParsedCommand) -> Result<(), String> {
+    let mut listed: Vec<(char, String)> = editor
+        .registers
+        .iter()
+        .map(|(name, text)| (name, text.text.clone()))
+        .collect();
+    for name in [':', '%', '/'] {
+        if let Ok(text) = editor.register_text(Some(name)) {
+            listed.push((name, text.text));
+        }
+    }
+
+    let description = listed
+        .into_iter()
+        .filter(|(name, _)| {
+            command
+                .argument
+                .as_ref()
+                .is_none_or(|names| names.contains(*name))
+        })
+        .map(|(name, text)| format!("\"{name} {}", text.replace('\n', "^J")))
+        .collect::<Vec<String>>()
+        .join("  ");
+
+    if description.is_empty() {
+        return Err(String::from("Nothing in registers"));
+    }
+    editor.show_message(description);
+    Ok(())
+}
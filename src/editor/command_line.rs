@@ -0,0 +1,169 @@
+use crossterm::event::{
+    KeyCode::{self, Char},
+    KeyEvent, KeyModifiers,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// What the editor should do after the command line handled a key.
+pub enum CommandLineAction {
+    Continue,
+    Execute(String),
+    Cancel,
+}
+
+/// Text typed after `:`, with its own caret and a history of executed
+/// commands that can be browsed with the arrow keys.
+#[derive(Default)]
+pub struct CommandLine {
+    text: String,
+    caret: usize,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    history_prefix: String,
+}
+
+impl CommandLine {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Screen column of the caret, counted from the start of the text.
+    pub fn caret_column(&self) -> usize {
+        self.text[..self.byte_index(self.caret)].width()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.caret = 0;
+        self.history_index = None;
+    }
+
+    /// Replaces the text, leaving the caret after it.
+    pub fn set_text(&mut self, text: &str) {
+        self.clear();
+        self.text.push_str(text);
+        self.caret = self.char_count();
+    }
+
+    pub fn handle_key(&mut self, event: KeyEvent) -> CommandLineAction {
+        let control = event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match event.code {
+            Char('b') if control => self.caret = 0,
+            Char('e') if control => self.caret = self.char_count(),
+            Char('u') if control => {
+                self.text.replace_range(..self.byte_index(self.caret), "");
+                self.caret = 0;
+            }
+            Char('w') if control => self.delete_word_backward(),
+            Char(c) if !control => {
+                self.text.insert(self.byte_index(self.caret), c);
+                self.caret += 1;
+            }
+            KeyCode::Backspace if self.text.is_empty() => return CommandLineAction::Cancel,
+            KeyCode::Backspace if self.caret > 0 => {
+                self.caret -= 1;
+                self.text.remove(self.byte_index(self.caret));
+            }
+            KeyCode::Delete if self.caret < self.char_count() => {
+                self.text.remove(self.byte_index(self.caret));
+            }
+            KeyCode::Left => self.caret = self.caret.saturating_sub(1),
+            KeyCode::Right => self.caret = std::cmp::min(self.caret + 1, self.char_count()),
+            KeyCode::Home => self.caret = 0,
+            KeyCode::End => self.caret = self.char_count(),
+            KeyCode::Up => self.browse_history(false),
+            KeyCode::Down => self.browse_history(true),
+            KeyCode::Enter => {
+                let command = std::mem::take(&mut self.text);
+                self.add_to_history(&command);
+                self.clear();
+                return CommandLineAction::Execute(command);
+            }
+            KeyCode::Esc => return CommandLineAction::Cancel,
+            _ => (),
+        }
+
+        CommandLineAction::Continue
+    }
+
+    /// The last command executed, which the `:` register holds.
+    pub fn last_command(&self) -> Option<&str> {
+        self.history.last().map(String::as_str)
+    }
+
+    fn delete_word_backward(&mut self) {
+        let before_caret: Vec<char> = self.text.chars().take(self.caret).collect();
+        let trailing_spaces = before_caret
+            .iter()
+            .rev()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        let word_length = before_caret
+            .iter()
+            .rev()
+            .skip(trailing_spaces)
+            .take_while(|c| !c.is_whitespace())
+            .count();
+        let start = self.caret - trailing_spaces - word_length;
+
+        self.text
+            .replace_range(self.byte_index(start)..self.byte_index(self.caret), "");
+        self.caret = start;
+    }
+
+    /// Walks the history entries starting with the text typed before the
+    /// history was first browsed, like vim does.
+    fn browse_history(&mut self, newer: bool) {
+        if self.history_index.is_none() {
+            self.history_prefix = self.text.clone();
+        }
+
+        let matches = |entry: &&String| entry.starts_with(&self.history_prefix);
+        let next_index = match (self.history_index, newer) {
+            (None, false) => self.history.iter().rposition(|entry| matches(&entry)),
+            (Some(index), false) => self.history[..index]
+                .iter()
+                .rposition(|entry| matches(&entry)),
+            (Some(index), true) => self.history[index + 1..]
+                .iter()
+                .position(|entry| matches(&entry))
+                .map(|position| index + 1 + position),
+            (None, true) => return,
+        };
+
+        match (next_index, newer) {
+            (Some(index), _) => {
+                self.history_index = Some(index);
+                self.text = self.history[index].clone();
+            }
+            (None, true) => {
+                self.history_index = None;
+                self.text = self.history_prefix.clone();
+            }
+            (None, false) => return,
+        }
+
+        self.caret = self.char_count();
+    }
+
+    fn add_to_history(&mut self, command: &str) {
+        if command.trim().is_empty() {
+            return;
+        }
+
+        self.history.retain(|entry| entry != command);
+        self.history.push(command.to_string());
+    }
+
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    io::Error,
+    time::{Duration, Instant},
+};
+
+use super::terminal::{self, Position};
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(5);
+
+struct Message {
+    text: String,
+    is_error: bool,
+    shown_at: Instant,
+}
+
+/// The last row of the screen, showing feedback and errors until they time
+/// out.
+#[derive(Default)]
+pub struct MessageBar {
+    message: Option<Message>,
+}
+
+impl MessageBar {
+    pub fn show(&mut self, text: String) {
+        self.set_message(text, false);
+    }
+
+    pub fn show_error(&mut self, text: String) {
+        self.set_message(text, true);
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+    }
+
+    /// Time left until the current message expires, if there is one.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.message
+            .as_ref()
+            .map(|message| MESSAGE_DURATION.saturating_sub(message.shown_at.elapsed()))
+    }
+
+    pub fn draw(&mut self, row: usize) -> Result<(), Error> {
+        if self.time_left() == Some(Duration::ZERO) {
+            self.message = None;
+        }
+
+        terminal::move_cursor_to(&Position { x: 0, y: row })?;
+
+        match &self.message {
+            Some(message) if message.is_error => terminal::print_error(&message.text),
+            Some(message) => terminal::print(&message.text),
+            None => terminal::clear_line(),
+        }
+    }
+
+    fn set_message(&mut self, text: String, is_error: bool) {
+        self.message = Some(Message {
+            text,
+            is_error,
+            shown_at: Instant::now(),
+        });
+    }
+}
@@ -1,30 +1,106 @@
-use std::panic::{set_hook, take_hook};
+use std::{
+    panic::{set_hook, take_hook},
+    path::Path,
+};
 
 use log::error;
 
+use clipboard::Clipboard;
+use command_line::{CommandLine, CommandLineAction};
 use crossterm::event::{
-    read,
+    poll, read,
     Event::{self, Key},
     KeyCode::{self, Char},
     KeyEvent, KeyModifiers, MouseEventKind,
 };
-use terminal::MovementDirection;
-use view::View;
+use message_bar::MessageBar;
+use normal_command::{
+    Action, CommandParser, InsertEdit, NormalCommand, Operator, ParseResult, RepeatableChange,
+    Target,
+};
+use registers::Registers;
+use status_bar::{DocumentStatus, StatusBar};
+use terminal::{MovementDirection, Position};
+use user_configuration::StatusLineFormat;
+use view::{CaseChange, Motion, MotionResult, SearchDirection, SelectedText, SelectionKind, View};
 
+mod clipboard;
+mod command;
+mod command_line;
+mod message_bar;
+mod normal_command;
+mod registers;
+mod status_bar;
 mod terminal;
 mod user_configuration;
 mod view;
 
+/// Rows below the text used by the status bar and the message bar.
+const RESERVED_ROWS: u16 = 2;
+
 pub struct Editor {
     user_controls: user_configuration::UserControls,
     current_mode: EditorMode,
     should_quit: bool,
     view: View,
+    status_bar: StatusBar,
+    command_line: CommandLine,
+    search_line: CommandLine,
+    message_bar: MessageBar,
+    /// Keys typed so far of a normal or visual mode command.
+    pending_command: CommandParser,
+    /// The last change, made again by `.`.
+    last_change: Option<RepeatableChange>,
+    /// The change being typed in insert mode, which becomes the last change
+    /// when leaving it.
+    inserting: Option<RepeatableChange>,
+    registers: Registers,
+    clipboard: Clipboard,
 }
 
 enum EditorMode {
     Normal,
     Insert,
+    Visual,
+    VisualLine,
+    VisualBlock,
+    Command,
+    Search(SearchDirection),
+    ConfirmQuit,
+    ConfirmSubstitute,
+}
+
+impl EditorMode {
+    fn name(&self) -> &'static str {
+        match self {
+            EditorMode::Normal => "NORMAL",
+            EditorMode::Insert => "INSERT",
+            EditorMode::Visual => "VISUAL",
+            EditorMode::VisualLine => "V-LINE",
+            EditorMode::VisualBlock => "V-BLOCK",
+            EditorMode::Command => "COMMAND",
+            EditorMode::Search(_) => "SEARCH",
+            EditorMode::ConfirmQuit | EditorMode::ConfirmSubstitute => "CONFIRM",
+        }
+    }
+
+    fn visual(kind: SelectionKind) -> Self {
+        match kind {
+            SelectionKind::Characterwise => EditorMode::Visual,
+            SelectionKind::Linewise => EditorMode::VisualLine,
+            SelectionKind::Blockwise => EditorMode::VisualBlock,
+        }
+    }
+
+    /// Shape of the selection made in a visual mode.
+    fn selection_kind(&self) -> Option<SelectionKind> {
+        match self {
+            EditorMode::Visual => Some(SelectionKind::Characterwise),
+            EditorMode::VisualLine => Some(SelectionKind::Linewise),
+            EditorMode::VisualBlock => Some(SelectionKind::Blockwise),
+            _ => None,
+        }
+    }
 }
 
 impl Drop for Editor {
@@ -45,18 +121,52 @@ impl Editor {
             current_hook(panic_info);
         }));
 
+        let user_configuration = user_configuration::get_user_configuration().expect(
+            "Couldn't load custom or default configuration, JSON file isn't formatted correctly",
+        );
+        let status_line_format = user_configuration
+            .status_line
+            .as_deref()
+            .map(StatusLineFormat::parse)
+            .transpose();
+
         let mut editor = Editor {
-            user_controls: user_configuration::get_user_controls().expect(
-                "Couldn't load custom or default configuration, JSON file isn't formatted correctly",
-            ),
+            user_controls: user_configuration.controls,
             current_mode: EditorMode::Normal,
             should_quit: false,
             view: View::default(),
+            status_bar: StatusBar::default(),
+            command_line: CommandLine::default(),
+            search_line: CommandLine::default(),
+            message_bar: MessageBar::default(),
+            pending_command: CommandParser::default(),
+            last_change: None,
+            inserting: None,
+            registers: Registers::default(),
+            clipboard: Clipboard::new(user_configuration.clipboard),
         };
 
+        match status_line_format {
+            Ok(Some(format)) => editor.status_bar = StatusBar::new(format),
+            Ok(None) => (),
+            Err(err) => editor.show_error(err),
+        }
+
+        let (terminal_width, terminal_height) = crossterm::terminal::size()?;
+        editor.resize(terminal_width, terminal_height);
+
+        let syntax_errors = editor.view.load_syntax_definitions();
+        for err in &syntax_errors {
+            error!("Couldn't load {err}");
+        }
+        if let Some(err) = syntax_errors.first() {
+            editor.show_error(format!("Couldn't load {err}"));
+        }
+
         if let Some(file) = file {
             if let Err(err) = editor.view.load_file(file.to_string()) {
                 error!("Couldn't open file: {err}");
+                editor.show_error(format!("Couldn't open {file}: {err}"));
             }
         }
 
@@ -74,6 +184,10 @@ impl Editor {
                 break;
             }
 
+            if !self.wait_for_event()? {
+                continue;
+            }
+
             match read() {
                 Ok(event) => self.handle_event(event)?,
                 Err(err) => {
@@ -86,12 +200,45 @@ impl Editor {
         Ok(())
     }
 
+    /// Blocks until there is an event to read, returning early when the
+    /// current message expires so the screen can be refreshed.
+    fn wait_for_event(&self) -> Result<bool, std::io::Error> {
+        match self.message_bar.time_left() {
+            Some(time_left) => poll(time_left),
+            None => Ok(true),
+        }
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        self.view
+            .update_terminal_size(width, height.saturating_sub(RESERVED_ROWS));
+    }
+
     fn handle_event(&mut self, event: Event) -> Result<(), std::io::Error> {
         if let Event::Resize(width, height) = event {
-            self.view.update_terminal_size(width, height);
+            self.resize(width, height);
         } else if let Event::Mouse(mouse_event) = event {
             self.handle_mouse_events(mouse_event.kind);
+        } else if let Event::FocusGained = event {
+            self.status_bar.refresh_file_info();
         } else if let Key(key_event) = event {
+            match self.current_mode {
+                EditorMode::Command => return self.evaluate_command_event(key_event),
+                EditorMode::Search(direction) => {
+                    self.evaluate_search_event(key_event, direction);
+                    return Ok(());
+                }
+                EditorMode::ConfirmQuit => {
+                    self.evaluate_quit_confirmation(key_event);
+                    return Ok(());
+                }
+                EditorMode::ConfirmSubstitute => {
+                    self.evaluate_substitute_confirmation(key_event);
+                    return Ok(());
+                }
+                _ => (),
+            }
+
             match key_event.code {
                 KeyCode::PageUp
                 | KeyCode::PageDown
@@ -101,49 +248,674 @@ impl Editor {
                 | KeyCode::Right
                 | KeyCode::Up
                 | KeyCode::Down => self.handle_movement_keys(&key_event.code)?,
-                Char('q') if key_event.modifiers == KeyModifiers::CONTROL => {
-                    self.should_quit = true
-                }
+                Char('q') if key_event.modifiers == KeyModifiers::CONTROL => self.request_quit(),
+                Char('s') if key_event.modifiers == KeyModifiers::CONTROL => self.save(),
                 _ => match self.current_mode {
                     EditorMode::Normal => self.evaluate_normal_event(key_event)?,
                     EditorMode::Insert => self.evaluate_insert_event(key_event)?,
+                    EditorMode::Visual | EditorMode::VisualLine | EditorMode::VisualBlock => {
+                        self.evaluate_visual_event(key_event)?;
+                    }
+                    _ => (),
                 },
             }
+
+            // Moving the caret changes what's selected.
+            if self.current_mode.selection_kind().is_some() {
+                self.view.needs_redraw = true;
+            }
         }
 
         Ok(())
     }
 
     fn evaluate_normal_event(&mut self, event: KeyEvent) -> Result<(), std::io::Error> {
-        if let Char(c) = event.code {
-            if self.user_controls.move_left == c {
-                self.view.move_caret(terminal::MovementDirection::Left, 1)?;
-            } else if self.user_controls.move_up == c {
-                self.view.move_caret(terminal::MovementDirection::Up, 1)?;
-            } else if self.user_controls.move_down == c {
-                self.view.move_caret(terminal::MovementDirection::Down, 1)?;
-            } else if self.user_controls.move_right == c {
-                self.view
-                    .move_caret(terminal::MovementDirection::Right, 1)?;
-            } else if self.user_controls.insert_mode == c {
-                self.change_to_insert_mode();
+        let Char(c) = event.code else {
+            self.pending_command.clear();
+            return Ok(());
+        };
+
+        if event.modifiers == KeyModifiers::CONTROL {
+            self.pending_command.clear();
+            if c == 'r' {
+                let redone = self.view.redo();
+                self.show_history_message(redone, "Already at newest change");
+            } else if c == 'v' {
+                self.change_to_visual_mode(SelectionKind::Blockwise);
+            }
+            return Ok(());
+        }
+
+        match self.pending_command.push(c, &self.user_controls, false) {
+            ParseResult::Complete(command) => {
+                self.execute_normal_command(command);
+                self.record_change(command);
             }
+            ParseResult::Error(err) => self.show_error(err),
+            ParseResult::Pending | ParseResult::Invalid => (),
         }
 
         Ok(())
     }
 
+    fn execute_normal_command(&mut self, command: NormalCommand) {
+        let NormalCommand {
+            register,
+            count,
+            action,
+        } = command;
+
+        match action {
+            Action::Move(motion) => {
+                self.move_by(motion, count);
+            }
+            Action::Operate(operator, target) => {
+                if matches!(operator, Operator::Delete | Operator::Change) {
+                    if let Err(err) = registers::check_writable(register) {
+                        self.show_error(err);
+                        return;
+                    }
+                }
+                let selected = match target {
+                    Target::Motion(motion) => {
+                        let result =
+                            self.view
+                                .select_motion(motion, count, operator == Operator::Change);
+                        self.show_motion_result(motion, result)
+                    }
+                    Target::Lines => self.view.select_lines(count.unwrap_or(1)),
+                    Target::Object(object) => match self.view.select_object(object, count) {
+                        Some(selected) => {
+                            // There's still somewhere to type in an empty
+                            // object, like `ci(` in `()`.
+                            if !selected && operator == Operator::Change {
+                                self.change_to_insert_mode();
+                            }
+                            selected
+                        }
+                        None => false,
+                    },
+                    Target::Selection => false,
+                };
+                if selected {
+                    self.apply_operator(operator, register);
+                }
+            }
+            Action::Key(c) => self.evaluate_normal_key(c, register, count),
+            // Objects are only selected in visual mode.
+            Action::Select(_) => (),
+            Action::KeyPair(first, second) => match (first, second) {
+                ('g', '-') => {
+                    let travelled = self.view.travel_history(false);
+                    self.show_history_message(travelled, "Already at oldest change");
+                }
+                ('g', '+') => {
+                    let travelled = self.view.travel_history(true);
+                    self.show_history_message(travelled, "Already at newest change");
+                }
+                ('m', name) => self.set_mark(name),
+                _ => (),
+            },
+        }
+    }
+
+    /// Runs the normal mode keys that take no motion.
+    fn evaluate_normal_key(&mut self, c: char, register: Option<char>, count: Option<usize>) {
+        if self.user_controls.insert_mode == c {
+            self.change_to_insert_mode();
+        } else if self.user_controls.command_mode == c {
+            self.change_to_command_mode();
+        } else if self.user_controls.undo == c {
+            let undone = self.view.undo();
+            self.show_history_message(undone, "Already at oldest change");
+        } else if c == '.' {
+            self.repeat_last_change(count);
+        } else if c == 'p' || c == 'P' {
+            match self.register_text(register) {
+                Ok(text) => self.view.put(&text, c == 'P', count.unwrap_or(1)),
+                Err(err) => self.show_error(err),
+            }
+        } else if c == '/' {
+            self.change_to_search_mode(SearchDirection::Forward);
+        } else if c == '?' {
+            self.change_to_search_mode(SearchDirection::Backward);
+        } else if c == 'v' {
+            self.change_to_visual_mode(SelectionKind::Characterwise);
+        } else if c == 'V' {
+            self.change_to_visual_mode(SelectionKind::Linewise);
+        }
+    }
+
+    /// Moves the caret as a motion does, returning whether it moved.
+    fn move_by(&mut self, motion: Motion, count: Option<usize>) -> bool {
+        let result = self.view.apply_motion(motion, count, false);
+        self.show_motion_result(motion, result)
+    }
+
+    /// Shows the notice or error of a motion, returning whether it
+    /// succeeded. Searching again clears what the last search showed.
+    fn show_motion_result(&mut self, motion: Motion, result: MotionResult) -> bool {
+        let succeeded = result.is_ok();
+        match result {
+            Ok(Some(message)) | Err(Some(message)) => self.show_error(message),
+            Ok(None) if matches!(motion, Motion::SearchNext { .. }) => self.message_bar.clear(),
+            _ => (),
+        }
+        succeeded
+    }
+
+    fn set_mark(&mut self, name: char) {
+        if let Err(err) = self.view.set_mark(name) {
+            self.show_error(err);
+        }
+    }
+
+    /// Extends the selection with the motions and applies operators to it.
+    fn evaluate_visual_event(&mut self, event: KeyEvent) -> Result<(), std::io::Error> {
+        let Char(c) = event.code else {
+            self.pending_command.clear();
+            match event.code {
+                KeyCode::Esc => self.change_to_normal_mode(),
+                KeyCode::Delete => self.delete_selection(None),
+                _ => (),
+            }
+            return Ok(());
+        };
+
+        if event.modifiers == KeyModifiers::CONTROL {
+            self.pending_command.clear();
+            match c {
+                'v' => self.switch_visual_mode(SelectionKind::Blockwise),
+                'c' => self.change_to_normal_mode(),
+                _ => (),
+            }
+            return Ok(());
+        }
+
+        let command = match self.pending_command.push(c, &self.user_controls, true) {
+            ParseResult::Complete(command) => command,
+            ParseResult::Error(err) => {
+                self.show_error(err);
+                return Ok(());
+            }
+            ParseResult::Pending | ParseResult::Invalid => return Ok(()),
+        };
+
+        match command.action {
+            Action::Move(motion) => {
+                self.move_by(motion, command.count);
+            }
+            Action::Operate(operator, _) => {
+                if operator == Operator::Change {
+                    if let Err(err) = registers::check_writable(command.register) {
+                        self.show_error(err);
+                        return Ok(());
+                    }
+                }
+                let repeated = self.selection_command(operator, command.register);
+                self.apply_operator(operator, command.register);
+                if let Some(repeated) = repeated {
+                    self.record_change(repeated);
+                }
+            }
+            Action::Key(c) if self.user_controls.command_mode == c => {
+                self.change_to_normal_mode();
+                self.change_to_command_mode();
+                self.command_line.set_text("'<,'>");
+            }
+            Action::Key(c) => match c {
+                'v' => self.switch_visual_mode(SelectionKind::Characterwise),
+                'V' => self.switch_visual_mode(SelectionKind::Linewise),
+                'o' => self.view.swap_selection_ends(),
+                'p' | 'P' => self.put_over_selection(command.register, c == 'P'),
+                _ => (),
+            },
+            Action::Select(object) => {
+                self.view.select_object(object, command.count);
+                // Objects of lines make the selection linewise, and others
+                // characterwise.
+                if let Some(kind) = self.view.selection_kind() {
+                    self.current_mode = EditorMode::visual(kind);
+                }
+            }
+            Action::KeyPair('m', name) => self.set_mark(name),
+            Action::KeyPair(..) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Remembers a command that changed the buffer for `.`, or starts
+    /// recording what's typed after it when it went on to insert mode.
+    fn record_change(&mut self, command: NormalCommand) {
+        let changes_buffer = match command.action {
+            Action::Operate(operator, _) => operator != Operator::Yank,
+            Action::Key(c) => c == self.user_controls.insert_mode || c == 'p' || c == 'P',
+            _ => false,
+        };
+        if !changes_buffer {
+            return;
+        }
+
+        let change = RepeatableChange {
+            command,
+            inserted: Vec::new(),
+        };
+        if matches!(self.current_mode, EditorMode::Insert) {
+            self.inserting = Some(change);
+        } else {
+            self.last_change = Some(change);
+        }
+    }
+
+    /// The normal mode command acting on as much text as the selection, so
+    /// `.` repeats a visual mode change from the caret. Only selections of
+    /// lines, or of characters within a line, are repeated.
+    fn selection_command(
+        &self,
+        operator: Operator,
+        register: Option<char>,
+    ) -> Option<NormalCommand> {
+        let (start, end) = self.view.selection_ends()?;
+        let (target, count) = match self.view.selection_kind()? {
+            SelectionKind::Linewise => (Target::Lines, end.y - start.y + 1),
+            SelectionKind::Characterwise if start.y == end.y => {
+                (Target::Motion(Motion::Right), end.x - start.x + 1)
+            }
+            _ => return None,
+        };
+
+        Some(NormalCommand {
+            register,
+            count: Some(count),
+            action: Action::Operate(operator, target),
+        })
+    }
+
+    /// Makes the last change again, `count` times over instead of its own
+    /// count when one is given.
+    fn repeat_last_change(&mut self, count: Option<usize>) {
+        let Some(mut change) = self.last_change.clone() else {
+            return;
+        };
+        if count.is_some() {
+            change.command.count = count;
+        }
+
+        self.execute_normal_command(change.command);
+        if matches!(self.current_mode, EditorMode::Insert) {
+            for _ in 0..self.insert_repetitions(&change) {
+                self.replay_insert(&change.inserted);
+            }
+            self.change_to_normal_mode();
+        }
+        self.last_change = Some(change);
+    }
+
+    /// How many times the text of a change is typed, which is its count for
+    /// inserting and once for changing text.
+    fn insert_repetitions(&self, change: &RepeatableChange) -> usize {
+        match change.command.action {
+            Action::Key(c) if c == self.user_controls.insert_mode => {
+                change.command.count.unwrap_or(1)
+            }
+            _ => 1,
+        }
+    }
+
+    fn replay_insert(&mut self, edits: &[InsertEdit]) {
+        for edit in edits {
+            match edit {
+                InsertEdit::Char(c) => self.view.insert_char(*c),
+                InsertEdit::Tab => self.view.insert_tab(),
+                InsertEdit::Newline => self.view.insert_newline(),
+                InsertEdit::DeleteBackward => self.view.delete_backward(),
+                InsertEdit::Delete => self.view.delete(),
+            }
+        }
+    }
+
+    /// Leaves insert mode, typing the text again for an insert with a count
+    /// and keeping the change for `.`.
+    fn finish_insert(&mut self) {
+        if let Some(change) = self.inserting.take() {
+            for _ in 1..self.insert_repetitions(&change) {
+                self.replay_insert(&change.inserted);
+            }
+            self.last_change = Some(change);
+        }
+        self.change_to_normal_mode();
+    }
+
+    /// Applies an operator to the selection, made in visual mode or by a
+    /// motion, and goes back to normal mode or on to insert mode.
+    fn apply_operator(&mut self, operator: Operator, register: Option<char>) {
+        match operator {
+            Operator::Delete => self.delete_selection(register),
+            Operator::Yank => self.yank_selection(register),
+            Operator::Change => {
+                if let Some(changed) = self.view.change_selection() {
+                    self.store_delete(register, changed);
+                }
+                self.change_to_insert_mode();
+            }
+            Operator::ShiftRight | Operator::ShiftLeft => {
+                self.view.shift_selection(operator == Operator::ShiftLeft);
+                self.change_to_normal_mode();
+            }
+            Operator::ToggleCase | Operator::Lowercase | Operator::Uppercase => {
+                let case_change = match operator {
+                    Operator::ToggleCase => CaseChange::Toggle,
+                    Operator::Lowercase => CaseChange::Lower,
+                    _ => CaseChange::Upper,
+                };
+                self.view.change_selection_case(case_change);
+                self.change_to_normal_mode();
+            }
+            Operator::Reindent => {
+                self.view.reindent_selection();
+                self.change_to_normal_mode();
+            }
+        }
+    }
+
+    fn delete_selection(&mut self, register: Option<char>) {
+        if let Err(err) = registers::check_writable(register) {
+            self.show_error(err);
+        } else if let Some(deleted) = self.view.delete_selection() {
+            self.store_delete(register, deleted);
+        }
+        self.change_to_normal_mode();
+    }
+
+    fn yank_selection(&mut self, register: Option<char>) {
+        if let Some(yanked) = self.view.yank_selection() {
+            let line_count = yanked.line_count();
+            let message = match yanked.kind {
+                SelectionKind::Blockwise => format!("block of {line_count} lines yanked"),
+                _ => format!("{line_count} lines yanked"),
+            };
+            self.store_yank(register, yanked);
+            if line_count > 2 {
+                self.show_message(message);
+            }
+        }
+        self.change_to_normal_mode();
+    }
+
+    /// Replaces the selection with a register, which `p` then fills with
+    /// what was selected while `P` leaves it alone.
+    fn put_over_selection(&mut self, register: Option<char>, keep_register: bool) {
+        match self.register_text(register) {
+            Ok(text) => {
+                if let Some(replaced) = self.view.put_over_selection(&text) {
+                    if !keep_register {
+                        self.store_delete(None, replaced);
+                    }
+                }
+            }
+            Err(err) => self.show_error(err),
+        }
+        self.change_to_normal_mode();
+    }
+
+    /// Text of a register, the unnamed one by default. The read-only
+    /// registers come from the editor: `/` is the last search pattern, `:`
+    /// the last command line and `%` the file name.
+    fn register_text(&self, register: Option<char>) -> Result<SelectedText, String> {
+        let name = register.unwrap_or(registers::UNNAMED);
+        let characters = |text: &str| SelectedText {
+            text: text.to_string(),
+            kind: SelectionKind::Characterwise,
+        };
+
+        let text = match name {
+            '/' => self.view.last_search_pattern().map(characters),
+            ':' => self.command_line.last_command().map(characters),
+            '%' => self.view.buffer.file_name().as_deref().map(characters),
+            '+' | '*' => self.clipboard_text(name)?,
+            _ => self.registers.get(name).cloned(),
+        };
+        text.filter(|text| !text.text.is_empty())
+            .ok_or_else(|| format!("Nothing in register {name}"))
+    }
+
+    /// Text of the system clipboard from the paste command, which is
+    /// linewise when it ends with a line break. Without a paste command, or
+    /// when the clipboard still has what was copied from here, it's the
+    /// text last copied to it in its original shape.
+    fn clipboard_text(&self, name: char) -> Result<Option<SelectedText>, String> {
+        let copied = self.registers.get(name);
+        let Some(pasted) = self.clipboard.paste()? else {
+            return Ok(copied.cloned());
+        };
+
+        match copied {
+            Some(copied) if copied.text == pasted => Ok(Some(copied.clone())),
+            _ => {
+                let kind = if pasted.ends_with('\n') {
+                    SelectionKind::Linewise
+                } else {
+                    SelectionKind::Characterwise
+                };
+                Ok(Some(SelectedText { text: pasted, kind }))
+            }
+        }
+    }
+
+    fn store_yank(&mut self, register: Option<char>, text: SelectedText) {
+        self.copy_to_clipboard(register, &text);
+        if let Err(err) = self.registers.store_yank(register, text) {
+            self.show_error(err);
+        }
+    }
+
+    fn store_delete(&mut self, register: Option<char>, text: SelectedText) {
+        self.copy_to_clipboard(register, &text);
+        if let Err(err) = self.registers.store_delete(register, text) {
+            self.show_error(err);
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, register: Option<char>, text: &SelectedText) {
+        if !register.is_some_and(|name| registers::CLIPBOARD.contains(&name)) {
+            return;
+        }
+        if let Err(err) = self.clipboard.copy(&text.text) {
+            error!("{err}");
+            self.show_error(err);
+        }
+    }
+
     fn evaluate_insert_event(&mut self, event: KeyEvent) -> Result<(), std::io::Error> {
+        let edit = match event.code {
+            Char(c) if !event.modifiers.contains(KeyModifiers::CONTROL) => InsertEdit::Char(c),
+            KeyCode::Tab => InsertEdit::Tab,
+            KeyCode::Enter => InsertEdit::Newline,
+            KeyCode::Backspace => InsertEdit::DeleteBackward,
+            KeyCode::Delete => InsertEdit::Delete,
+            KeyCode::Esc => {
+                self.finish_insert();
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        self.replay_insert(&[edit]);
+        if let Some(change) = &mut self.inserting {
+            change.inserted.push(edit);
+        }
+        Ok(())
+    }
+
+    fn evaluate_command_event(&mut self, event: KeyEvent) -> Result<(), std::io::Error> {
+        match self.command_line.handle_key(event) {
+            CommandLineAction::Continue => (),
+            CommandLineAction::Execute(command) => {
+                self.change_to_normal_mode();
+                if let Err(err) = command::execute(self, &command) {
+                    self.show_error(err);
+                }
+            }
+            CommandLineAction::Cancel => self.change_to_normal_mode(),
+        }
+
+        self.view.needs_redraw = true;
+        Ok(())
+    }
+
+    /// Edits the search prompt, moving the caret to the first match of the
+    /// pattern after every key.
+    fn evaluate_search_event(&mut self, event: KeyEvent, direction: SearchDirection) {
+        match self.search_line.handle_key(event) {
+            CommandLineAction::Continue => {
+                self.view.preview_search(self.search_line.text(), direction);
+            }
+            CommandLineAction::Execute(pattern) => {
+                self.change_to_normal_mode();
+                let result = self.view.finish_search(&pattern, direction);
+                self.show_search_result(result);
+            }
+            CommandLineAction::Cancel => {
+                self.change_to_normal_mode();
+                self.view.cancel_search();
+            }
+        }
+
+        self.view.needs_redraw = true;
+    }
+
+    fn show_search_result(&mut self, result: Result<Option<String>, String>) {
+        match result {
+            Ok(Some(notice)) | Err(notice) => self.show_error(notice),
+            Ok(None) => self.message_bar.clear(),
+        }
+    }
+
+    fn request_quit(&mut self) {
+        if self.view.buffer.modified {
+            self.current_mode = EditorMode::ConfirmQuit;
+            self.view.needs_redraw = true;
+        } else {
+            self.should_quit = true;
+        }
+    }
+
+    fn evaluate_quit_confirmation(&mut self, event: KeyEvent) {
         match event.code {
-            Char(_) => self.view.needs_redraw = true,
-            KeyCode::Esc => self.change_to_normal_mode(),
+            Char('y') => {
+                self.change_to_normal_mode();
+                self.save();
+                self.should_quit = !self.view.buffer.modified;
+            }
+            Char('q') if event.modifiers == KeyModifiers::CONTROL => self.should_quit = true,
+            Char('n') => self.should_quit = true,
+            Char('c') | KeyCode::Esc => {
+                self.change_to_normal_mode();
+                self.view.needs_redraw = true;
+            }
             _ => (),
         }
+    }
+
+    /// Answers the question asked for every match of `:s///c`.
+    fn evaluate_substitute_confirmation(&mut self, event: KeyEvent) {
+        let has_next_match = match event.code {
+            Char('y') => self.view.replace_current_match(),
+            Char('n') => self.view.skip_current_match(),
+            Char('l') => {
+                self.view.replace_current_match();
+                false
+            }
+            Char('a') => {
+                self.view.replace_remaining_matches();
+                false
+            }
+            Char('q') | KeyCode::Esc => false,
+            Char('c') if event.modifiers == KeyModifiers::CONTROL => false,
+            _ => true,
+        };
+
+        if !has_next_match {
+            self.change_to_normal_mode();
+            self.finish_substitution();
+        }
+    }
+
+    fn finish_substitution(&mut self) {
+        if let Some(report) = self.view.finish_substitution() {
+            self.show_message(report);
+        }
+    }
 
+    fn save(&mut self) {
+        match self.view.save() {
+            Ok(()) => self.show_written_message(),
+            Err(err) => {
+                error!("Couldn't save file: {err}");
+                self.show_error(format!("Couldn't save file: {err}"));
+            }
+        }
+    }
+
+    fn save_as(&mut self, file_path: &str) {
+        match self.view.save_as(file_path.into()) {
+            Ok(()) => self.show_written_message(),
+            Err(err) => {
+                error!("Couldn't save file as {file_path}: {err}");
+                self.show_error(format!("Couldn't save file as {file_path}: {err}"));
+            }
+        }
+    }
+
+    /// Writes a copy of the text to another file, like `:w {file}`.
+    fn write_to(&mut self, file_path: &str) -> Result<(), String> {
+        if let Err(err) = self.view.buffer.write_to(Path::new(file_path)) {
+            error!("Couldn't write file {file_path}: {err}");
+            return Err(format!("Couldn't write file {file_path}: {err}"));
+        }
+        let line_count = self.view.buffer.line_count();
+        self.show_message(format!("\"{file_path}\" {line_count}L written"));
         Ok(())
     }
 
+    fn show_written_message(&mut self) {
+        self.status_bar.refresh_file_info();
+        let file_name = self.view.buffer.file_name().unwrap_or_default();
+        let line_count = self.view.buffer.line_count();
+        self.show_message(format!("\"{file_name}\" {line_count}L written"));
+    }
+
+    fn show_history_message(&mut self, moved: bool, limit_message: &str) {
+        if moved {
+            let state = self.view.buffer.history_state();
+            self.show_message(format!("Change #{state}"));
+        } else {
+            self.show_error(limit_message.to_string());
+        }
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.message_bar.show(message);
+    }
+
+    fn show_error(&mut self, message: String) {
+        self.message_bar.show_error(message);
+    }
+
     fn handle_movement_keys(&mut self, key: &KeyCode) -> Result<(), std::io::Error> {
+        self.view.commit_change();
+        // Moving while inserting starts a new insert, like in vim, so `.`
+        // only types what comes after.
+        if let Some(change) = &mut self.inserting {
+            change.command = NormalCommand {
+                register: None,
+                count: None,
+                action: Action::Key(self.user_controls.insert_mode),
+            };
+            change.inserted.clear();
+        }
+
         match key {
             KeyCode::PageUp => self.view.move_caret(MovementDirection::Top, 0)?,
             KeyCode::PageDown => self.view.move_caret(MovementDirection::Bottom, 0)?,
@@ -178,27 +950,123 @@ impl Editor {
 
     fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
         if self.should_quit {
-            Self::quit()
-        } else if self.view.needs_redraw {
+            return Self::quit();
+        }
+
+        if self.view.needs_redraw {
             if self.view.buffer.is_empty() {
                 self.view.render_title_screen()?;
             } else {
                 self.view.render()?;
             }
+        }
 
-            terminal::move_cursor_to(&self.view.position)
-        } else {
-            Ok(())
+        self.draw_status_bar()?;
+        self.draw_bottom_line()?;
+
+        match self.current_mode {
+            EditorMode::Command => terminal::move_cursor_to(&Position {
+                x: self.command_line.caret_column() + 1,
+                y: self.bottom_row(),
+            }),
+            EditorMode::Search(_) => terminal::move_cursor_to(&Position {
+                x: self.search_line.caret_column() + 1,
+                y: self.bottom_row(),
+            }),
+            _ => terminal::move_cursor_to(&self.view.position),
         }
     }
 
+    fn draw_status_bar(&mut self) -> Result<(), std::io::Error> {
+        let status = DocumentStatus {
+            mode: self.current_mode.name(),
+            file_path: self.view.buffer.file_path.clone(),
+            file_type: self.view.file_type(),
+            encoding: self.view.buffer.encoding(),
+            line_ending: self.view.buffer.line_ending.name(),
+            indentation: self.view.indentation(),
+            modified: self.view.buffer.modified,
+            line: self.view.location.y + 1,
+            line_count: self.view.buffer.line_count(),
+            column: self.view.location.x + 1,
+            selection_size: self.view.selection_size(),
+        };
+
+        self.status_bar.draw(
+            &status,
+            self.view.width() as usize,
+            self.view.height() as usize,
+        )
+    }
+
+    fn draw_bottom_line(&mut self) -> Result<(), std::io::Error> {
+        let text = match self.current_mode {
+            EditorMode::Command => format!(":{}", self.command_line.text()),
+            EditorMode::Search(direction) => {
+                format!("{}{}", direction.prompt(), self.search_line.text())
+            }
+            EditorMode::ConfirmQuit => String::from("Save changes? y/n/cancel"),
+            EditorMode::ConfirmSubstitute => self.view.substitution_prompt().unwrap_or_default(),
+            _ => return self.message_bar.draw(self.bottom_row()),
+        };
+
+        terminal::move_cursor_to(&Position {
+            x: 0,
+            y: self.bottom_row(),
+        })?;
+        terminal::print(&text)
+    }
+
+    fn bottom_row(&self) -> usize {
+        (self.view.height() + RESERVED_ROWS - 1) as usize
+    }
+
     fn change_to_insert_mode(&mut self) {
         self.current_mode = EditorMode::Insert;
         terminal::change_to_insert_caret();
     }
 
     fn change_to_normal_mode(&mut self) {
+        self.view.end_selection();
+        self.view.commit_change();
         self.current_mode = EditorMode::Normal;
         terminal::change_to_normal_caret();
     }
+
+    fn change_to_visual_mode(&mut self, kind: SelectionKind) {
+        self.current_mode = EditorMode::visual(kind);
+        self.view.start_selection(kind);
+    }
+
+    /// Switches to another visual mode, or back to normal mode when already
+    /// in that one.
+    fn switch_visual_mode(&mut self, kind: SelectionKind) {
+        if self.current_mode.selection_kind() == Some(kind) {
+            self.change_to_normal_mode();
+        } else {
+            self.current_mode = EditorMode::visual(kind);
+            self.view.set_selection_kind(kind);
+        }
+    }
+
+    fn change_to_command_mode(&mut self) {
+        self.current_mode = EditorMode::Command;
+        self.command_line.clear();
+        self.message_bar.clear();
+        self.view.needs_redraw = true;
+    }
+
+    fn change_to_substitute_confirmation(&mut self) {
+        self.current_mode = EditorMode::ConfirmSubstitute;
+        self.message_bar.clear();
+        self.view.needs_redraw = true;
+    }
+
+    fn change_to_search_mode(&mut self, direction: SearchDirection) {
+        self.current_mode = EditorMode::Search(direction);
+        self.search_line.clear();
+        self.message_bar.clear();
+        self.view.start_search();
+        self.view.needs_redraw = true;
+    }
 }
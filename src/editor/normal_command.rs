@@ -0,0 +1,363 @@
+use super::{
+    registers,
+    user_configuration::UserControls,
+    view::{CharSearch, Motion, ObjectKind, TextObject},
+};
+
+/// An operator waiting for the text it acts on, like `d` in `dw`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    ShiftRight,
+    ShiftLeft,
+    ToggleCase,
+    Lowercase,
+    Uppercase,
+    Reindent,
+}
+
+impl Operator {
+    fn from_key(c: char) -> Option<Self> {
+        let operator = match c {
+            'd' => Operator::Delete,
+            'c' => Operator::Change,
+            'y' => Operator::Yank,
+            '>' => Operator::ShiftRight,
+            '<' => Operator::ShiftLeft,
+            '=' => Operator::Reindent,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    /// The operators typed after `g`.
+    fn from_g_key(c: char) -> Option<Self> {
+        let operator = match c {
+            '~' => Operator::ToggleCase,
+            'u' => Operator::Lowercase,
+            'U' => Operator::Uppercase,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    /// The key typed again to act on lines, like the second `d` of `dd` or
+    /// the `u` of `guu`.
+    fn key(self) -> char {
+        match self {
+            Operator::Delete => 'd',
+            Operator::Change => 'c',
+            Operator::Yank => 'y',
+            Operator::ShiftRight => '>',
+            Operator::ShiftLeft => '<',
+            Operator::ToggleCase => '~',
+            Operator::Lowercase => 'u',
+            Operator::Uppercase => 'U',
+            Operator::Reindent => '=',
+        }
+    }
+}
+
+/// What an operator acts on.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    /// From the caret to where a motion goes.
+    Motion(Motion),
+    /// Lines from the caret line on, asked for by doubling the operator.
+    Lines,
+    Object(TextObject),
+    /// The visual mode selection.
+    Selection,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// Selecting a text object in visual mode.
+    Select(TextObject),
+    /// A key without a grammar of its own, like `p` or `u`.
+    Key(char),
+    /// A key followed by the one it applies to, like `ma` or `g-`.
+    KeyPair(char, char),
+}
+
+/// A complete normal or visual mode command, like `"a2d3w`.
+#[derive(Clone, Copy)]
+pub struct NormalCommand {
+    pub register: Option<char>,
+    /// The count typed before the command, multiplied by the one typed
+    /// before its motion.
+    pub count: Option<usize>,
+    pub action: Action,
+}
+
+pub enum ParseResult {
+    Pending,
+    Complete(NormalCommand),
+    /// Keys that make no command, which vim only beeps for.
+    Invalid,
+    Error(String),
+}
+
+/// Reads the keys of a command as they're typed, following vim's
+/// `["x][count] operator [count] motion` grammar.
+#[derive(Default)]
+pub struct CommandParser {
+    register: Option<char>,
+    count: Option<usize>,
+    operator: Option<Operator>,
+    motion_count: Option<usize>,
+    /// A key waiting for the next one, like `g` or `f`.
+    prefix: Option<char>,
+}
+
+impl CommandParser {
+    /// Adds a key to the command. In `visual` mode, operators act on the
+    /// selection right away instead of waiting for a motion.
+    pub fn push(&mut self, c: char, controls: &UserControls, visual: bool) -> ParseResult {
+        let result = self.parse(c, controls, visual);
+        if !matches!(result, ParseResult::Pending) {
+            self.clear();
+        }
+        result
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn parse(&mut self, c: char, controls: &UserControls, visual: bool) -> ParseResult {
+        if let Some(prefix) = self.prefix.take() {
+            return self.parse_prefixed(prefix, c, visual);
+        }
+
+        if let Some(digit) = c.to_digit(10) {
+            let count = if self.operator.is_some() {
+                &mut self.motion_count
+            } else {
+                &mut self.count
+            };
+            if digit > 0 || count.is_some() {
+                *count = Some(
+                    count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit as usize),
+                );
+                return ParseResult::Pending;
+            }
+        }
+
+        if self.operator.is_none() {
+            let is_control = if visual {
+                c == controls.command_mode
+            } else {
+                [controls.insert_mode, controls.command_mode, controls.undo].contains(&c)
+            };
+            if is_control {
+                return self.complete(Action::Key(c));
+            }
+        }
+
+        if let Some(motion) = motion(c, controls) {
+            return self.complete_motion(motion);
+        }
+
+        match c {
+            'i' | 'a' if self.operator.is_some() || visual => {
+                self.prefix = Some(c);
+                ParseResult::Pending
+            }
+            '"' | 'm' if self.operator.is_some() => ParseResult::Invalid,
+            '"' | 'g' | 'f' | 't' | 'F' | 'T' | '\'' | '`' | 'm' => {
+                self.prefix = Some(c);
+                ParseResult::Pending
+            }
+            _ => match self.operator {
+                Some(operator) if c == operator.key() => {
+                    self.complete(Action::Operate(operator, Target::Lines))
+                }
+                Some(_) => ParseResult::Invalid,
+                None if visual => self.parse_visual_key(c),
+                None => self.parse_normal_key(c),
+            },
+        }
+    }
+
+    fn parse_prefixed(&mut self, prefix: char, c: char, visual: bool) -> ParseResult {
+        match (prefix, c) {
+            ('"', name) if registers::is_valid_name(name) => {
+                self.register = Some(name);
+                ParseResult::Pending
+            }
+            ('"', name) => ParseResult::Error(format!("Invalid register name: {name}")),
+            ('i' | 'a', c) => {
+                let Some(kind) = object(c) else {
+                    return ParseResult::Invalid;
+                };
+                let object = TextObject {
+                    kind,
+                    around: prefix == 'a',
+                };
+                match self.operator {
+                    Some(operator) => {
+                        self.complete(Action::Operate(operator, Target::Object(object)))
+                    }
+                    None => self.complete(Action::Select(object)),
+                }
+            }
+            ('g', 'g') => self.complete_motion(Motion::FirstLine),
+            ('g', 'j') => self.complete_motion(Motion::RowDown),
+            ('g', 'k') => self.complete_motion(Motion::RowUp),
+            ('g', '~' | 'u' | 'U') => {
+                let operator = Operator::from_g_key(c);
+                match (self.operator, operator) {
+                    (Some(pending), Some(operator)) if pending == operator => {
+                        self.complete(Action::Operate(operator, Target::Lines))
+                    }
+                    (None, Some(operator)) if visual => {
+                        self.complete(Action::Operate(operator, Target::Selection))
+                    }
+                    (None, Some(operator)) => {
+                        self.operator = Some(operator);
+                        ParseResult::Pending
+                    }
+                    _ => ParseResult::Invalid,
+                }
+            }
+            ('f' | 't' | 'F' | 'T', target) => self.complete_motion(Motion::FindChar(CharSearch {
+                target,
+                forward: prefix.is_lowercase(),
+                till: prefix.eq_ignore_ascii_case(&'t'),
+            })),
+            ('\'' | '`', name) => self.complete_motion(Motion::Mark {
+                name,
+                exact: prefix == '`',
+            }),
+            ('g' | 'm', _) if self.operator.is_none() => self.complete(Action::KeyPair(prefix, c)),
+            _ => ParseResult::Invalid,
+        }
+    }
+
+    /// Operators and their shorthands, like `x` for `dl`.
+    fn parse_normal_key(&mut self, c: char) -> ParseResult {
+        if let Some(operator) = Operator::from_key(c) {
+            self.operator = Some(operator);
+            return ParseResult::Pending;
+        }
+
+        let action = match c {
+            'x' => Action::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+            'X' => Action::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+            'D' => Action::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+            'C' => Action::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+            's' => Action::Operate(Operator::Change, Target::Motion(Motion::Right)),
+            'S' => Action::Operate(Operator::Change, Target::Lines),
+            'Y' => Action::Operate(Operator::Yank, Target::Lines),
+            _ => Action::Key(c),
+        };
+        self.complete(action)
+    }
+
+    /// Operators acting on the selection.
+    fn parse_visual_key(&mut self, c: char) -> ParseResult {
+        let operator = match c {
+            'd' | 'x' => Operator::Delete,
+            'c' | 's' => Operator::Change,
+            '~' => Operator::ToggleCase,
+            'u' => Operator::Lowercase,
+            'U' => Operator::Uppercase,
+            _ => match Operator::from_key(c) {
+                Some(operator) => operator,
+                None => return self.complete(Action::Key(c)),
+            },
+        };
+        self.complete(Action::Operate(operator, Target::Selection))
+    }
+
+    fn complete_motion(&mut self, motion: Motion) -> ParseResult {
+        match self.operator {
+            Some(operator) => self.complete(Action::Operate(operator, Target::Motion(motion))),
+            None => self.complete(Action::Move(motion)),
+        }
+    }
+
+    fn complete(&self, action: Action) -> ParseResult {
+        let count = match (self.count, self.motion_count) {
+            (Some(count), Some(motion_count)) => Some(count.saturating_mul(motion_count)),
+            (count, motion_count) => count.or(motion_count),
+        };
+
+        ParseResult::Complete(NormalCommand {
+            register: self.register,
+            count,
+            action,
+        })
+    }
+}
+
+/// The motions typed with a single key.
+fn motion(c: char, controls: &UserControls) -> Option<Motion> {
+    let motion = match c {
+        _ if c == controls.move_left => Motion::Left,
+        _ if c == controls.move_right => Motion::Right,
+        _ if c == controls.move_up => Motion::Up,
+        _ if c == controls.move_down => Motion::Down,
+        'w' | 'W' => Motion::WordForward { big: c == 'W' },
+        'b' | 'B' => Motion::WordBackward { big: c == 'B' },
+        'e' | 'E' => Motion::WordEnd {
+            big: c == 'E',
+            stay: false,
+        },
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'G' => Motion::LastLine,
+        ';' | ',' => Motion::RepeatFind { reverse: c == ',' },
+        '}' => Motion::ParagraphForward,
+        '{' => Motion::ParagraphBackward,
+        'n' | 'N' => Motion::SearchNext { reverse: c == 'N' },
+        _ => return None,
+    };
+    Some(motion)
+}
+
+/// The text objects typed after `i` or `a`.
+fn object(c: char) -> Option<ObjectKind> {
+    let bracket = |open, close| ObjectKind::Bracket { open, close };
+    let kind = match c {
+        'w' | 'W' => ObjectKind::Word { big: c == 'W' },
+        's' => ObjectKind::Sentence,
+        'p' => ObjectKind::Paragraph,
+        '"' | '\'' | '`' => ObjectKind::Quote(c),
+        '(' | ')' | 'b' => bracket('(', ')'),
+        '{' | '}' | 'B' => bracket('{', '}'),
+        '[' | ']' => bracket('[', ']'),
+        '<' | '>' => bracket('<', '>'),
+        't' => ObjectKind::Tag,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// An edit made in insert mode, kept to make it again with `.`.
+#[derive(Clone, Copy)]
+pub enum InsertEdit {
+    Char(char),
+    Tab,
+    Newline,
+    DeleteBackward,
+    Delete,
+}
+
+/// The last command that changed the buffer, for `.` to repeat.
+#[derive(Clone)]
+pub struct RepeatableChange {
+    pub command: NormalCommand,
+    /// What was typed after the command, for the commands that go on to
+    /// insert mode.
+    pub inserted: Vec<InsertEdit>,
+}
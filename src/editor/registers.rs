@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use super::view::{SelectedText, SelectionKind};
+
+/// Register used when none is given, holding the last yanked or deleted
+/// text.
+pub const UNNAMED: char = '"';
+/// Register that forgets what's written to it.
+const BLACK_HOLE: char = '_';
+/// Register holding the last yank made without naming a register.
+const LAST_YANK: char = '0';
+/// Registers `"1` to `"9`, holding the last deletes from newest to oldest.
+const DELETE_HISTORY: [char; 9] = ['1', '2', '3', '4', '5', '6', '7', '8', '9'];
+/// Registers of the system clipboard, which share their text.
+pub const CLIPBOARD: [char; 2] = ['+', '*'];
+/// Registers filled by the editor: the last search pattern, the last
+/// command line and the file name.
+const READ_ONLY: [char; 3] = ['/', ':', '%'];
+
+/// Where yanked and deleted text goes, by register name.
+#[derive(Default)]
+pub struct Registers {
+    contents: BTreeMap<char, SelectedText>,
+}
+
+/// Whether `name` can be given with `"` before a command.
+pub fn is_valid_name(name: char) -> bool {
+    name == UNNAMED
+        || name == BLACK_HOLE
+        || name.is_ascii_alphanumeric()
+        || CLIPBOARD.contains(&name)
+        || READ_ONLY.contains(&name)
+}
+
+/// Key a register's text is kept under, the lowercase letter for the
+/// appending uppercase names.
+fn key(name: char) -> char {
+    if CLIPBOARD.contains(&name) {
+        CLIPBOARD[0]
+    } else {
+        name.to_ascii_lowercase()
+    }
+}
+
+/// Fails for the registers that can't be written, so a delete can be
+/// refused before the text is lost.
+pub fn check_writable(name: Option<char>) -> Result<(), String> {
+    match name {
+        Some(name) if READ_ONLY.contains(&name) => Err(format!("Register is read-only: {name}")),
+        Some(name) if !is_valid_name(name) => Err(format!("Invalid register name: {name}")),
+        _ => Ok(()),
+    }
+}
+
+impl Registers {
+    /// Stores yanked text in the named register, or in `"0` when there is
+    /// none. The unnamed register gets it too.
+    pub fn store_yank(&mut self, name: Option<char>, text: SelectedText) -> Result<(), String> {
+        match name.filter(|name| *name != UNNAMED) {
+            Some(name) => self.store(name, text),
+            None => {
+                self.contents.insert(LAST_YANK, text.clone());
+                self.contents.insert(UNNAMED, text);
+                Ok(())
+            }
+        }
+    }
+
+    /// Stores deleted text in the named register, or in `"1` when there is
+    /// none, moving the older deletes up to `"9`. The unnamed register gets
+    /// it too.
+    pub fn store_delete(&mut self, name: Option<char>, text: SelectedText) -> Result<(), String> {
+        match name.filter(|name| *name != UNNAMED) {
+            Some(name) => self.store(name, text),
+            None => {
+                for pair in DELETE_HISTORY.windows(2).rev() {
+                    match self.contents.remove(&pair[0]) {
+                        Some(shifted) => self.contents.insert(pair[1], shifted),
+                        None => self.contents.remove(&pair[1]),
+                    };
+                }
+                self.contents.insert(DELETE_HISTORY[0], text.clone());
+                self.contents.insert(UNNAMED, text);
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes to a named register, appending to it when the name is
+    /// uppercase.
+    fn store(&mut self, name: char, text: SelectedText) -> Result<(), String> {
+        check_writable(Some(name))?;
+        if name == BLACK_HOLE {
+            return Ok(());
+        }
+
+        let text = match self.contents.get(&key(name)) {
+            Some(existing) if name.is_ascii_uppercase() => append(existing, &text),
+            _ => text,
+        };
+        self.contents.insert(key(name), text.clone());
+        self.contents.insert(UNNAMED, text);
+        Ok(())
+    }
+
+    pub fn get(&self, name: char) -> Option<&SelectedText> {
+        self.contents.get(&key(name))
+    }
+
+    /// The registers holding text, in the order `:registers` lists them.
+    pub fn iter(&self) -> impl Iterator<Item = (char, &SelectedText)> {
+        let unnamed = self.contents.get_key_value(&UNNAMED);
+        let others = self.contents.iter().filter(|(name, _)| **name != UNNAMED);
+        unnamed
+            .into_iter()
+            .chain(others)
+            .map(|(name, text)| (*name, text))
+    }
+}
+
+/// Adds text to a register. Lines on either side make the register
+/// linewise, and a block gets the new text as more block lines.
+fn append(existing: &SelectedText, text: &SelectedText) -> SelectedText {
+    let mut joined = existing.text.clone();
+
+    let kind = if existing.kind == SelectionKind::Linewise || text.kind == SelectionKind::Linewise {
+        if !joined.ends_with('\n') {
+            joined.push('\n');
+        }
+        joined.push_str(&text.text);
+        if !joined.ends_with('\n') {
+            joined.push('\n');
+        }
+        SelectionKind::Linewise
+    } else if existing.kind == SelectionKind::Blockwise || text.kind == SelectionKind::Blockwise {
+        joined.push('\n');
+        joined.push_str(&text.text);
+        SelectionKind::Blockwise
+    } else {
+        joined.push_str(&text.text);
+        SelectionKind::Characterwise
+    };
+
+    SelectedText { text: joined, kind }
+}
@@ -0,0 +1,195 @@
+use std::{
+    env, fs,
+    io::Error,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use super::{
+    terminal::{self, Position},
+    user_configuration::{StatusField, StatusLineFormat},
+};
+
+/// What the status bar shows about the document being edited.
+pub struct DocumentStatus {
+    pub mode: &'static str,
+    pub file_path: Option<PathBuf>,
+    pub file_type: String,
+    pub encoding: &'static str,
+    pub line_ending: &'static str,
+    pub indentation: String,
+    pub modified: bool,
+    pub line: usize,
+    pub line_count: usize,
+    pub column: usize,
+    pub selection_size: Option<usize>,
+}
+
+/// How long the git branch is shown before reading it again, as it can be
+/// switched outside the editor.
+const GIT_BRANCH_LIFETIME: Duration = Duration::from_secs(5);
+
+/// What the status bar shows about the file from the disk, kept so
+/// redrawing doesn't read it every time.
+struct FileInfo {
+    file_path: Option<PathBuf>,
+    relative_path: String,
+    git_branch: Option<String>,
+    read_at: Instant,
+}
+
+impl FileInfo {
+    fn read(file_path: Option<&Path>) -> Self {
+        Self {
+            file_path: file_path.map(Path::to_path_buf),
+            relative_path: file_path.map_or(String::from("[No Name]"), StatusBar::relative_path),
+            git_branch: StatusBar::read_git_branch(file_path),
+            read_at: Instant::now(),
+        }
+    }
+
+    /// Whether this is the file shown, read recently enough.
+    fn is_current(&self, file_path: Option<&Path>) -> bool {
+        self.file_path.as_deref() == file_path && self.read_at.elapsed() < GIT_BRANCH_LIFETIME
+    }
+}
+
+/// The row under the text, laid out by the user's status line format.
+#[derive(Default)]
+pub struct StatusBar {
+    format: StatusLineFormat,
+    file_info: Option<FileInfo>,
+}
+
+impl StatusBar {
+    pub fn new(format: StatusLineFormat) -> Self {
+        Self {
+            format,
+            file_info: None,
+        }
+    }
+
+    /// Reads the file's path and git branch again on the next draw, for
+    /// when they may have changed, like after saving or coming back to the
+    /// terminal.
+    pub fn refresh_file_info(&mut self) {
+        self.file_info = None;
+    }
+
+    pub fn draw(&mut self, status: &DocumentStatus, width: usize, row: usize) -> Result<(), Error> {
+        let file_path = status.file_path.as_deref();
+        if !self
+            .file_info
+            .as_ref()
+            .is_some_and(|file_info| file_info.is_current(file_path))
+        {
+            self.file_info = None;
+        }
+        let file_info = self
+            .file_info
+            .get_or_insert_with(|| FileInfo::read(file_path));
+        let parts = self
+            .format
+            .render(|field| Self::field_value(field, status, file_info), width);
+
+        terminal::move_cursor_to(&Position { x: 0, y: row })?;
+        terminal::clear_line()?;
+        for part in parts {
+            terminal::print_styled(&part.text, part.style.foreground, part.style.background)?;
+        }
+
+        Ok(())
+    }
+
+    fn field_value(field: StatusField, status: &DocumentStatus, file_info: &FileInfo) -> String {
+        match field {
+            StatusField::FileName => status
+                .file_path
+                .as_ref()
+                .and_then(|file_path| file_path.file_name())
+                .map_or(String::from("[No Name]"), |file_name| {
+                    file_name.to_string_lossy().to_string()
+                }),
+            StatusField::RelativePath => file_info.relative_path.clone(),
+            StatusField::FileType => status.file_type.clone(),
+            StatusField::Encoding => status.encoding.to_string(),
+            StatusField::LineEnding => status.line_ending.to_string(),
+            StatusField::Indentation => status.indentation.clone(),
+            StatusField::Line => status.line.to_string(),
+            StatusField::LineCount => status.line_count.to_string(),
+            StatusField::Column => status.column.to_string(),
+            StatusField::Percentage => {
+                let percentage = (status.line * 100)
+                    .checked_div(status.line_count)
+                    .unwrap_or(100);
+                format!("{}%", std::cmp::min(percentage, 100))
+            }
+            StatusField::Mode => status.mode.to_string(),
+            StatusField::Modified => {
+                if status.modified {
+                    String::from(" [+]")
+                } else {
+                    String::new()
+                }
+            }
+            StatusField::GitBranch => file_info.git_branch.clone().unwrap_or_default(),
+            StatusField::Selection => status
+                .selection_size
+                .map_or(String::new(), |selection_size| selection_size.to_string()),
+        }
+    }
+
+    fn relative_path(file_path: &Path) -> String {
+        let absolute_path = fs::canonicalize(file_path).unwrap_or(file_path.to_path_buf());
+
+        env::current_dir()
+            .ok()
+            .and_then(|current_dir| {
+                absolute_path
+                    .strip_prefix(current_dir)
+                    .ok()
+                    .map(Path::to_path_buf)
+            })
+            .unwrap_or(file_path.to_path_buf())
+            .display()
+            .to_string()
+    }
+
+    /// Finds the `.git` entry above the file and reads its `HEAD`, showing
+    /// a short commit hash when the head is detached.
+    fn read_git_branch(file_path: Option<&Path>) -> Option<String> {
+        let start = match file_path {
+            Some(file_path) => fs::canonicalize(file_path)
+                .ok()?
+                .parent()
+                .map(Path::to_path_buf)?,
+            None => env::current_dir().ok()?,
+        };
+
+        let git_path = start
+            .ancestors()
+            .map(|directory| directory.join(".git"))
+            .find(|git_path| git_path.exists())?;
+
+        let git_directory = if git_path.is_file() {
+            let contents = fs::read_to_string(&git_path).ok()?;
+            let linked_directory = PathBuf::from(contents.strip_prefix("gitdir:")?.trim());
+            git_path.parent()?.join(linked_directory)
+        } else {
+            git_path
+        };
+
+        let head = fs::read_to_string(git_directory.join("HEAD")).ok()?;
+        let head = head.trim();
+
+        match head.strip_prefix("ref: ") {
+            Some(reference) => Some(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            ),
+            None => Some(head.chars().take(7).collect()),
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use std::{
     fmt::Display,
-    io::{stdout, Error, Write},
+    io::{stdout, Error, ErrorKind, Write},
 };
 
 use crossterm::{
+    event::{DisableFocusChange, EnableFocusChange},
     queue,
     terminal::{
         disable_raw_mode, enable_raw_mode, Clear, EnterAlternateScreen, LeaveAlternateScreen,
@@ -12,7 +13,7 @@ use crossterm::{
 };
 use log::error;
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -62,6 +63,85 @@ pub fn print(text: &str) -> Result<(), Error> {
     execute_queue()
 }
 
+/// Prints after what the line already shows, without clearing it.
+pub fn print_inline(text: &str) -> Result<(), Error> {
+    queue_command(crossterm::style::Print(text))?;
+    execute_queue()
+}
+
+/// Prints without clearing the line, in the given colors or in reverse
+/// video when there are none.
+pub fn print_styled(
+    text: &str,
+    foreground: Option<crossterm::style::Color>,
+    background: Option<crossterm::style::Color>,
+) -> Result<(), Error> {
+    if foreground.is_none() && background.is_none() {
+        queue_command(crossterm::style::SetAttribute(
+            crossterm::style::Attribute::Reverse,
+        ))?;
+    }
+    if let Some(foreground) = foreground {
+        queue_command(crossterm::style::SetForegroundColor(foreground))?;
+    }
+    if let Some(background) = background {
+        queue_command(crossterm::style::SetBackgroundColor(background))?;
+    }
+
+    queue_command(crossterm::style::Print(text))?;
+    queue_command(crossterm::style::SetAttribute(
+        crossterm::style::Attribute::Reset,
+    ))?;
+    queue_command(crossterm::style::ResetColor)?;
+    execute_queue()
+}
+
+pub fn print_error(text: &str) -> Result<(), Error> {
+    queue_command(Clear(crossterm::terminal::ClearType::CurrentLine))?;
+    queue_command(crossterm::style::SetForegroundColor(
+        crossterm::style::Color::Red,
+    ))?;
+    queue_command(crossterm::style::Print(text))?;
+    queue_command(crossterm::style::ResetColor)?;
+    execute_queue()
+}
+
+/// Sets the clipboard of the terminal with an OSC 52 sequence, which
+/// reaches the local clipboard over SSH. Fails when the sequence would be
+/// longer than `max_length`, as terminals drop or cut those.
+pub fn copy_to_clipboard(text: &str, max_length: usize) -> Result<(), Error> {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if sequence.len() > max_length {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} bytes is over the OSC 52 limit", sequence.len()),
+        ));
+    }
+
+    queue_command(crossterm::style::Print(sequence))?;
+    execute_queue()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                encoded.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
 pub fn move_cursor_to(position: &Position) -> Result<(), Error> {
     queue_command(crossterm::cursor::MoveTo(
         position.x as u16,
@@ -86,6 +166,7 @@ pub fn change_to_normal_caret() {
 
 pub fn terminate() -> Result<(), Error> {
     change_to_normal_caret();
+    queue_command(DisableFocusChange)?;
     queue_command(LeaveAlternateScreen)?;
     execute_queue()?;
     disable_raw_mode()?;
@@ -95,6 +176,7 @@ pub fn terminate() -> Result<(), Error> {
 pub fn initialize() -> Result<(), Error> {
     enable_raw_mode()?;
     queue_command(EnterAlternateScreen)?;
+    queue_command(EnableFocusChange)?;
     clear_screen()?;
     change_to_normal_caret();
     execute_queue()?;
@@ -1,12 +1,32 @@
 use std::{fs, io::Error};
 
+use crossterm::style::Color;
+use unicode_width::UnicodeWidthStr;
+
+/// Longest OSC 52 sequence sent by default, which most terminals accept.
+const DEFAULT_OSC52_LIMIT: usize = 100_000;
+const DEFAULT_STATUS_LINE: &str =
+    " {mode} | {file_name}{modified}{=}{filetype} | {indentation} | {line}/{line_count} : {column} ";
+
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+#[serde(default)]
+pub struct UserConfiguration {
+    #[serde(flatten)]
+    pub controls: UserControls,
+    pub status_line: Option<String>,
+    pub clipboard: ClipboardConfiguration,
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
 pub struct UserControls {
     pub move_left: char,
     pub move_right: char,
     pub move_up: char,
     pub move_down: char,
     pub insert_mode: char,
+    pub command_mode: char,
+    pub undo: char,
 }
 
 impl Default for UserControls {
@@ -17,22 +37,286 @@ impl Default for UserControls {
             move_up: 'k',
             move_down: 'j',
             insert_mode: 'i',
+            command_mode: ':',
+            undo: 'u',
+        }
+    }
+}
+
+/// How the `+` and `*` registers reach the system clipboard. Copies go
+/// through the terminal with OSC 52 unless it's turned off or the text is
+/// longer than `osc52_limit`, in which case `copy_command` gets the text on
+/// its standard input. `paste_command` prints the clipboard, since
+/// terminals can't be reliably asked for it.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ClipboardConfiguration {
+    pub osc52: bool,
+    pub osc52_limit: usize,
+    pub copy_command: Option<String>,
+    pub paste_command: Option<String>,
+}
+
+impl Default for ClipboardConfiguration {
+    fn default() -> Self {
+        Self {
+            osc52: true,
+            osc52_limit: DEFAULT_OSC52_LIMIT,
+            copy_command: None,
+            paste_command: None,
+        }
+    }
+}
+
+/// Values the status line format can refer to with `{name}`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StatusField {
+    FileName,
+    RelativePath,
+    FileType,
+    Encoding,
+    LineEnding,
+    Indentation,
+    Line,
+    LineCount,
+    Column,
+    Percentage,
+    Mode,
+    Modified,
+    GitBranch,
+    Selection,
+}
+
+impl StatusField {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name {
+            "file_name" => StatusField::FileName,
+            "relative_path" => StatusField::RelativePath,
+            "filetype" => StatusField::FileType,
+            "encoding" => StatusField::Encoding,
+            "line_ending" => StatusField::LineEnding,
+            "indentation" => StatusField::Indentation,
+            "line" => StatusField::Line,
+            "line_count" => StatusField::LineCount,
+            "column" => StatusField::Column,
+            "percentage" => StatusField::Percentage,
+            "mode" => StatusField::Mode,
+            "modified" => StatusField::Modified,
+            "git_branch" => StatusField::GitBranch,
+            "selection" => StatusField::Selection,
+            _ => return None,
+        };
+
+        Some(field)
+    }
+}
+
+/// Colors of a part of the status line. Parts without colors are drawn in
+/// reverse video.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct StatusStyle {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+pub struct StyledText {
+    pub text: String,
+    pub style: StatusStyle,
+}
+
+enum StatusItem {
+    Text(String),
+    Field(StatusField),
+    Style(StatusStyle),
+}
+
+/// A parsed status line format, like `{mode} {file_name}{=}{line}:{column}`.
+/// Text after `{=}` is aligned to the right, `{fg:color}` and `{bg:color}`
+/// change the colors of what follows, `{reset}` restores them and `{{` or
+/// `}}` print a brace.
+pub struct StatusLineFormat {
+    left: Vec<StatusItem>,
+    right: Vec<StatusItem>,
+}
+
+impl Default for StatusLineFormat {
+    fn default() -> Self {
+        Self::parse(DEFAULT_STATUS_LINE).expect("The default status line format is invalid")
+    }
+}
+
+impl StatusLineFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        let mut is_right_aligned = false;
+        let mut style = StatusStyle::default();
+        let mut text = String::new();
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(String::from("Unmatched } in status line format")),
+                '{' => {
+                    let Some((tag, rest)) = chars.as_str().split_once('}') else {
+                        return Err(String::from("Unclosed { in status line format"));
+                    };
+                    let tag = tag.to_string();
+                    chars = rest.chars();
+
+                    let items = if is_right_aligned {
+                        &mut right
+                    } else {
+                        &mut left
+                    };
+                    if !text.is_empty() {
+                        items.push(StatusItem::Text(std::mem::take(&mut text)));
+                    }
+
+                    if tag == "=" {
+                        if is_right_aligned {
+                            return Err(String::from("{=} used twice in status line format"));
+                        }
+                        is_right_aligned = true;
+                    } else if tag == "reset" {
+                        style = StatusStyle::default();
+                        items.push(StatusItem::Style(style));
+                    } else if let Some(color) = tag.strip_prefix("fg:") {
+                        style.foreground = Some(Self::parse_color(color)?);
+                        items.push(StatusItem::Style(style));
+                    } else if let Some(color) = tag.strip_prefix("bg:") {
+                        style.background = Some(Self::parse_color(color)?);
+                        items.push(StatusItem::Style(style));
+                    } else {
+                        let field = StatusField::from_name(&tag)
+                            .ok_or_else(|| format!("Unknown status line placeholder: {{{tag}}}"))?;
+                        items.push(StatusItem::Field(field));
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            let items = if is_right_aligned {
+                &mut right
+            } else {
+                &mut left
+            };
+            items.push(StatusItem::Text(text));
+        }
+
+        Ok(Self { left, right })
+    }
+
+    /// Fills the placeholders with `field_value` and lays the result out on
+    /// `width` columns, dropping the right side when both don't fit.
+    pub fn render(
+        &self,
+        field_value: impl Fn(StatusField) -> String,
+        width: usize,
+    ) -> Vec<StyledText> {
+        let (mut left, style) =
+            Self::render_items(&self.left, StatusStyle::default(), &field_value);
+        let (mut right, _) = Self::render_items(&self.right, style, &field_value);
+
+        if Self::width(&left) + Self::width(&right) > width {
+            right.clear();
+            Self::truncate(&mut left, width);
+        }
+
+        let padding = width.saturating_sub(Self::width(&left) + Self::width(&right));
+        left.push(StyledText {
+            text: " ".repeat(padding),
+            style,
+        });
+        left.append(&mut right);
+        left
+    }
+
+    fn render_items(
+        items: &[StatusItem],
+        mut style: StatusStyle,
+        field_value: &impl Fn(StatusField) -> String,
+    ) -> (Vec<StyledText>, StatusStyle) {
+        let mut parts = Vec::new();
+
+        for item in items {
+            let text = match item {
+                StatusItem::Text(text) => text.clone(),
+                StatusItem::Field(field) => field_value(*field),
+                StatusItem::Style(new_style) => {
+                    style = *new_style;
+                    continue;
+                }
+            };
+            parts.push(StyledText { text, style });
+        }
+
+        (parts, style)
+    }
+
+    fn width(parts: &[StyledText]) -> usize {
+        parts.iter().map(|part| part.text.width()).sum()
+    }
+
+    fn truncate(parts: &mut Vec<StyledText>, width: usize) {
+        let mut used_width = 0;
+
+        for part in parts.iter_mut() {
+            let mut truncated = String::new();
+            for c in part.text.chars() {
+                let char_width = c.to_string().width();
+                if used_width + char_width > width {
+                    break;
+                }
+                used_width += char_width;
+                truncated.push(c);
+            }
+            part.text = truncated;
+        }
+
+        parts.retain(|part| !part.text.is_empty());
+    }
+
+    /// Accepts crossterm color names like `dark_blue` and `#rrggbb` values.
+    fn parse_color(name: &str) -> Result<Color, String> {
+        let hex_color = name
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+
+        match hex_color {
+            Some(rgb) => Ok(Color::Rgb {
+                r: (rgb >> 16) as u8,
+                g: (rgb >> 8) as u8,
+                b: rgb as u8,
+            }),
+            None => Color::try_from(name).map_err(|_| format!("Unknown status line color: {name}")),
         }
     }
 }
 
-pub(crate) fn get_user_controls() -> Result<UserControls, Error> {
-    let controls: UserControls;
+pub(crate) fn get_user_configuration() -> Result<UserConfiguration, Error> {
+    let configuration: UserConfiguration;
 
     match read_config_file() {
-        Ok(file) => controls = serde_json::from_str(&file)?,
+        Ok(file) => configuration = serde_json::from_str(&file)?,
         Err(err) => match err.kind() {
-            std::io::ErrorKind::NotFound => return Ok(UserControls::default()),
+            std::io::ErrorKind::NotFound => return Ok(UserConfiguration::default()),
             _ => return Err(err),
         },
     }
 
-    Ok(controls)
+    Ok(configuration)
 }
 
 fn read_config_file() -> Result<String, Error> {
@@ -1,10 +1,834 @@
-#[derive(Default)]
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Error, ErrorKind, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+use regex::Regex;
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::super::terminal::Position;
+use super::history::{Edit, History, Revision};
+use super::search::{SearchDirection, SearchQuery, SearchResult};
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+/// Columns between tab stops when nothing else is configured.
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// Text of an open file stored in a rope, so edits anywhere in the file take
+/// logarithmic time and cloning the buffer shares the unchanged text.
+#[derive(Default, Clone)]
 pub struct Buffer {
-    pub contents: Vec<String>,
+    text: Rope,
+    history: History,
+    pub file_path: Option<PathBuf>,
+    pub modified: bool,
+    pub line_ending: LineEnding,
+    /// Whether the file starts with a byte order mark, which is kept out of
+    /// the text and written back before it.
+    pub byte_order_mark: bool,
+    /// First line edited since the last call to `take_first_changed_line`.
+    first_changed_line: Option<usize>,
+    /// Named locations set with `m`, moving with the lines around them.
+    marks: BTreeMap<char, Position>,
+    pub write_fixes: WriteFixes,
+}
+
+/// Changes made to the text every time it's written, as asked by an
+/// `.editorconfig` file.
+#[derive(Default, Clone)]
+pub struct WriteFixes {
+    pub trim_trailing_whitespace: bool,
+    /// Whether the text must end with a line break, or must not.
+    pub final_newline: Option<bool>,
+    /// Whether every line break is made the buffer's line ending.
+    pub consistent_line_endings: bool,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Unix,
+    Dos,
+}
+
+impl LineEnding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "unix",
+            LineEnding::Dos => "dos",
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "\n",
+            LineEnding::Dos => "\r\n",
+        }
+    }
 }
 
 impl Buffer {
+    pub fn load(file_path: &Path) -> Result<Self, Error> {
+        let file = File::open(file_path)?;
+        let mut text = Rope::from_reader(BufReader::new(file))?;
+        let line_ending = Self::detect_line_ending(&text);
+        let byte_order_mark = text.chars().next() == Some(BYTE_ORDER_MARK);
+        if byte_order_mark {
+            text.remove(0..1);
+        }
+
+        Ok(Self {
+            text,
+            history: History::default(),
+            file_path: Some(file_path.to_path_buf()),
+            modified: false,
+            line_ending,
+            byte_order_mark,
+            first_changed_line: None,
+            marks: BTreeMap::new(),
+            write_fixes: WriteFixes::default(),
+        })
+    }
+
+    /// Uses the line ending of the first line, as files rarely mix them.
+    fn detect_line_ending(text: &Rope) -> LineEnding {
+        match text.lines().next() {
+            Some(line) if line.len_chars() >= 2 && line.char(line.len_chars() - 2) == '\r' => {
+                LineEnding::Dos
+            }
+            _ => LineEnding::Unix,
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        let Some(file_path) = &self.file_path else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+        let file_path = file_path.clone();
+
+        Self::write_atomically(&file_path, &self.fixed_text(), self.byte_order_mark)?;
+        self.apply_write_fixes();
+        self.history.mark_saved();
+        self.modified = false;
+        Ok(())
+    }
+
+    pub fn save_as(&mut self, file_path: PathBuf) -> Result<(), Error> {
+        Self::write_atomically(&file_path, &self.fixed_text(), self.byte_order_mark)?;
+        self.apply_write_fixes();
+        self.file_path = Some(file_path);
+        self.history.mark_saved();
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Writes the text to another file, leaving the buffer's own file, its
+    /// modified flag and its history as they are.
+    pub fn write_to(&self, file_path: &Path) -> Result<(), Error> {
+        Self::write_atomically(file_path, &self.fixed_text(), self.byte_order_mark)
+    }
+
+    /// The text as it is written, with the write fixes made on a copy so the
+    /// buffer is only edited once the file is written.
+    fn fixed_text(&self) -> Rope {
+        let mut copy = Self {
+            text: self.text.clone(),
+            line_ending: self.line_ending,
+            write_fixes: self.write_fixes.clone(),
+            ..Self::default()
+        };
+        copy.apply_write_fixes();
+        copy.text
+    }
+
+    /// Edits the text as the write fixes ask, as one undo step.
+    fn apply_write_fixes(&mut self) {
+        let fixes = self.write_fixes.clone();
+
+        for line_index in 0..self.line_count() {
+            let text = self.line_text(line_index).unwrap_or_default();
+            let mut length = text.len();
+            if fixes.trim_trailing_whitespace {
+                let trimmed_length = text.trim_end().len();
+                self.replace(line_index, trimmed_length..length, "");
+                length = trimmed_length;
+            }
+
+            let line_break = self.line_break(line_index);
+            if fixes.consistent_line_endings
+                && !line_break.is_empty()
+                && line_break != self.line_ending.as_str()
+            {
+                self.replace(line_index, length..length + line_break.len(), "\n");
+            }
+        }
+
+        match fixes.final_newline {
+            Some(true) if !self.is_empty() && self.line_break(self.line_count() - 1).is_empty() => {
+                let last_line = self.line_count() - 1;
+                let length = self.line_text(last_line).unwrap_or_default().len();
+                self.replace(last_line, length..length, "\n");
+            }
+            Some(false) => {
+                while !self.is_empty() && !self.line_break(self.line_count() - 1).is_empty() {
+                    let last_line = self.line_count() - 1;
+                    let length = self.line_text(last_line).unwrap_or_default().len();
+                    let line_break_length = self.line_break(last_line).len();
+                    self.replace(last_line, length..length + line_break_length, "");
+                }
+            }
+            _ => (),
+        }
+
+        self.history.commit();
+    }
+
+    /// The line break ending a line as it is in the text, which is empty
+    /// for the last line unless the text ends with a line break.
+    fn line_break(&self, line_index: usize) -> &'static str {
+        let line = self.text.line(line_index);
+        let length = line.len_chars();
+        match (
+            length.checked_sub(2).map(|index| line.char(index)),
+            length.checked_sub(1).map(|index| line.char(index)),
+        ) {
+            (Some('\r'), Some('\n')) => "\r\n",
+            (_, Some('\n')) => "\n",
+            _ => "",
+        }
+    }
+
+    /// Name of the encoding the text is written in.
+    pub fn encoding(&self) -> &'static str {
+        if self.byte_order_mark {
+            "utf-8-bom"
+        } else {
+            "utf-8"
+        }
+    }
+
+    /// Replaces the history with the one stored for the file, if it was
+    /// stored for the same text the buffer holds.
+    pub fn read_history(&mut self) {
+        let Some(file_path) = &self.file_path else {
+            return;
+        };
+
+        match History::read(file_path, Self::content_hash(&self.text)) {
+            Ok(Some(history)) => self.history = history,
+            Ok(None) => (),
+            Err(err) => error!(
+                "Couldn't read undo history of {}: {err}",
+                file_path.display()
+            ),
+        }
+    }
+
+    pub fn write_history(&self) {
+        let Some(file_path) = &self.file_path else {
+            return;
+        };
+
+        if let Err(err) = self
+            .history
+            .write(file_path, Self::content_hash(&self.text))
+        {
+            error!(
+                "Couldn't write undo history of {}: {err}",
+                file_path.display()
+            );
+        }
+    }
+
+    /// FNV-1a hash of the text, stable between runs unlike the std hasher.
+    fn content_hash(text: &Rope) -> u64 {
+        text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Name of the file type, guessed from the file extension.
+    pub fn file_type(&self) -> String {
+        let extension = self
+            .file_path
+            .as_ref()
+            .and_then(|file_path| file_path.extension())
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            None | Some("txt") => "text",
+            Some("rs") => "rust",
+            Some("json") => "json",
+            Some("yml" | "yaml") => "yaml",
+            Some("toml") => "toml",
+            Some("md" | "markdown") => "markdown",
+            Some("c" | "h") => "c",
+            Some("py") => "python",
+            Some(extension) => return extension.to_string(),
+        }
+        .to_string()
+    }
+
+    pub fn file_name(&self) -> Option<String> {
+        self.file_path
+            .as_ref()
+            .map(|file_path| file_path.display().to_string())
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.contents.is_empty()
+        self.text.len_chars() == 0
+    }
+
+    /// Amount of lines in the buffer, not counting the empty line the rope
+    /// reports after a trailing line break.
+    pub fn line_count(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let last_char = self.text.char(self.text.len_chars() - 1);
+        self.text.len_lines() - usize::from(last_char == '\n')
+    }
+
+    pub fn line(&self, line_index: usize) -> Option<Line> {
+        self.line_text(line_index)
+            .map(|line| Line::from(line.as_str()))
+    }
+
+    /// Text of a line without its line break.
+    pub fn line_text(&self, line_index: usize) -> Option<String> {
+        if line_index >= self.line_count() {
+            return None;
+        }
+
+        let mut line = self.text.line(line_index).to_string();
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        Some(line)
+    }
+
+    pub fn line_length(&self, line_index: usize) -> usize {
+        self.line(line_index)
+            .map_or(0, |line| line.grapheme_count())
+    }
+
+    pub fn insert_char(&mut self, character: char, at: &Position) {
+        self.insert_text(&character.to_string(), at);
+    }
+
+    pub fn insert_newline(&mut self, at: &Position) {
+        self.insert_text(self.line_ending.as_str(), at);
+    }
+
+    /// Inserts text at a location, where `\n` stands for the line ending of
+    /// the buffer. A location on `line_count` is the end of the text.
+    pub fn insert(&mut self, text: &str, at: &Position) {
+        self.insert_text(&text.replace('\n', self.line_ending.as_str()), at);
+    }
+
+    fn insert_text(&mut self, text: &str, at: &Position) {
+        if at.y > self.line_count() {
+            return;
+        }
+
+        let char_index = self.char_index(at);
+        let edit = Edit::Insert {
+            char_index,
+            text: text.to_string(),
+        };
+        self.move_marks(&edit);
+        self.text.insert(char_index, text);
+        self.mark_changed(at.y);
+        self.history.record(edit, *at);
+        self.modified = true;
+    }
+
+    /// Deletes the grapheme at the given location, joining the next line
+    /// into the current one when the location is at the end of its line.
+    pub fn delete(&mut self, at: &Position) {
+        let Some(line) = self.line(at.y) else {
+            return;
+        };
+
+        let start = self.char_index(at);
+        let end = if at.x < line.grapheme_count() {
+            self.char_index(&Position {
+                x: at.x + 1,
+                y: at.y,
+            })
+        } else if at.y + 1 < self.line_count() {
+            self.text.line_to_char(at.y + 1)
+        } else {
+            return;
+        };
+
+        let edit = Edit::Remove {
+            char_index: start,
+            text: self.text.slice(start..end).to_string(),
+        };
+        self.move_marks(&edit);
+        self.text.remove(start..end);
+        self.mark_changed(at.y);
+        self.history.record(edit, *at);
+        self.modified = true;
+    }
+
+    /// Removes the text from one location up to another, where a location
+    /// on the line past the last one stands for the end of the text.
+    pub fn remove_between(&mut self, from: &Position, to: &Position) {
+        let start = self.char_index(from);
+        let end = self.char_index(to);
+        if start >= end {
+            return;
+        }
+
+        let edit = Edit::Remove {
+            char_index: start,
+            text: self.text.slice(start..end).to_string(),
+        };
+        self.move_marks(&edit);
+        self.text.remove(start..end);
+        self.mark_changed(from.y);
+        self.history.record(edit, *from);
+        self.modified = true;
+    }
+
+    /// Replaces a byte range of a line with `text`, where `\n` stands for
+    /// the line ending of the buffer.
+    pub fn replace(&mut self, line_index: usize, bytes: Range<usize>, text: &str) {
+        let Some(line) = self.line(line_index) else {
+            return;
+        };
+        if bytes.is_empty() && text.is_empty() {
+            return;
+        }
+
+        let line_start = self.text.line_to_byte(line_index);
+        let start = self.text.byte_to_char(line_start + bytes.start);
+        let end = self.text.byte_to_char(line_start + bytes.end);
+        let caret = Position {
+            x: line.grapheme_index_at_byte(bytes.start),
+            y: line_index,
+        };
+
+        if start < end {
+            let edit = Edit::Remove {
+                char_index: start,
+                text: self.text.slice(start..end).to_string(),
+            };
+            self.move_marks(&edit);
+            self.text.remove(start..end);
+            self.history.record(edit, caret);
+        }
+
+        if !text.is_empty() {
+            let text = text.replace('\n', self.line_ending.as_str());
+            let edit = Edit::Insert {
+                char_index: start,
+                text: text.clone(),
+            };
+            self.move_marks(&edit);
+            self.text.insert(start, &text);
+            self.history.record(edit, caret);
+        }
+
+        self.mark_changed(line_index);
+        self.modified = true;
+    }
+
+    pub fn set_mark(&mut self, name: char, at: Position) {
+        self.marks.insert(name, at);
+    }
+
+    pub fn mark(&self, name: char) -> Option<Position> {
+        self.marks.get(&name).copied()
+    }
+
+    pub fn marks(&self) -> impl Iterator<Item = (char, Position)> + '_ {
+        self.marks.iter().map(|(name, at)| (*name, *at))
+    }
+
+    /// Keeps marks on their lines when an edit, not applied yet, inserts
+    /// or removes line breaks before them. Marks on removed lines move to
+    /// the line the removal starts on.
+    fn move_marks(&mut self, edit: &Edit) {
+        let (Edit::Insert { char_index, text } | Edit::Remove { char_index, text }) = edit;
+        let line_break_count = text.matches('\n').count();
+        if line_break_count == 0 || self.marks.is_empty() {
+            return;
+        }
+
+        let edit_line = self.text.char_to_line(*char_index);
+        for mark in self.marks.values_mut() {
+            if mark.y <= edit_line {
+                continue;
+            }
+            match edit {
+                Edit::Insert { .. } => mark.y += line_break_count,
+                Edit::Remove { .. } if mark.y <= edit_line + line_break_count => {
+                    mark.y = edit_line;
+                }
+                Edit::Remove { .. } => mark.y -= line_break_count,
+            }
+        }
+    }
+
+    fn mark_changed(&mut self, line_index: usize) {
+        self.first_changed_line = Some(
+            self.first_changed_line
+                .map_or(line_index, |line| std::cmp::min(line, line_index)),
+        );
+    }
+
+    /// Returns and forgets the first line edited since the last call, so
+    /// what depends on the text can update from there.
+    pub fn take_first_changed_line(&mut self) -> Option<usize> {
+        self.first_changed_line.take()
+    }
+
+    /// Ends the current undo step, grouping every edit since the last call.
+    pub fn commit_change(&mut self) {
+        self.history.commit();
+    }
+
+    pub fn history_state(&self) -> usize {
+        self.history.current_state()
+    }
+
+    pub fn undo(&mut self) -> Option<Position> {
+        let revision = self.history.undo()?;
+        Some(self.apply_revision(revision))
+    }
+
+    pub fn redo(&mut self) -> Option<Position> {
+        let revision = self.history.redo()?;
+        Some(self.apply_revision(revision))
+    }
+
+    /// Moves to the chronologically previous or next text state, even if it
+    /// lives in another branch of the undo tree.
+    pub fn travel_history(&mut self, newer: bool) -> Option<Position> {
+        let revision = self.history.travel(newer)?;
+        Some(self.apply_revision(revision))
+    }
+
+    fn apply_revision(&mut self, revision: Revision) -> Position {
+        for edit in revision.edits {
+            let (Edit::Insert { char_index, .. } | Edit::Remove { char_index, .. }) = &edit;
+            self.mark_changed(self.text.char_to_line(*char_index));
+            self.move_marks(&edit);
+
+            match edit {
+                Edit::Insert { char_index, text } => self.text.insert(char_index, &text),
+                Edit::Remove { char_index, text } => {
+                    self.text
+                        .remove(char_index..char_index + text.chars().count());
+                }
+            }
+        }
+
+        self.modified = !self.history.is_at_saved_state();
+        revision.caret
+    }
+
+    /// Finds the next match of `query` before or after `from`, wrapping
+    /// around the end of the buffer and back to the line of `from`.
+    pub fn find(&self, query: &SearchQuery, from: &Position) -> Option<SearchResult> {
+        let line_count = self.line_count();
+        if line_count == 0 {
+            return None;
+        }
+
+        for step in 0..=line_count {
+            let (line_index, wrapped) = match query.direction {
+                SearchDirection::Forward => {
+                    ((from.y + step) % line_count, from.y + step >= line_count)
+                }
+                SearchDirection::Backward => (
+                    (from.y + line_count - step % line_count) % line_count,
+                    step > from.y,
+                ),
+            };
+            let Some(line) = self.line(line_index) else {
+                continue;
+            };
+            let mut starts = line
+                .find_matches(&query.regex)
+                .into_iter()
+                .map(|matched| matched.start);
+
+            let found = match (query.direction, step) {
+                (SearchDirection::Forward, 0) => starts.find(|start| *start > from.x),
+                (SearchDirection::Forward, _) => starts.next(),
+                (SearchDirection::Backward, 0) => starts.rfind(|start| *start < from.x),
+                (SearchDirection::Backward, _) => starts.next_back(),
+            };
+
+            if let Some(x) = found {
+                return Some(SearchResult {
+                    location: Position { x, y: line_index },
+                    wrapped,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Converts a grapheme location into a char index of the rope.
+    fn char_index(&self, at: &Position) -> usize {
+        let line_start = self.text.line_to_byte(at.y);
+        let byte_in_line = self.line(at.y).map_or(0, |line| line.byte_index(at.x));
+        self.text.byte_to_char(line_start + byte_in_line)
+    }
+
+    /// Writes into a temporary file next to the target and renames it over
+    /// the target, so a failed write never leaves a truncated file behind.
+    fn write_atomically(file_path: &Path, text: &Rope, byte_order_mark: bool) -> Result<(), Error> {
+        let target_path = fs::canonicalize(file_path).unwrap_or(file_path.to_path_buf());
+        let Some(file_name) = target_path.file_name() else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Invalid file name"));
+        };
+        let temp_path =
+            target_path.with_file_name(format!(".{}.hecto~", file_name.to_string_lossy()));
+        let permissions = fs::metadata(&target_path)
+            .ok()
+            .map(|metadata| metadata.permissions());
+
+        let write_result = (|| {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+            if byte_order_mark {
+                write!(writer, "{BYTE_ORDER_MARK}")?;
+            }
+            text.write_to(&mut writer)?;
+            writer.into_inner()?.sync_all()?;
+
+            if let Some(permissions) = permissions {
+                fs::set_permissions(&temp_path, permissions)?;
+            }
+
+            fs::rename(&temp_path, &target_path)
+        })();
+
+        if write_result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        write_result
+    }
+}
+
+#[derive(Clone)]
+struct TextFragment {
+    start_byte: usize,
+    width: usize,
+    replacement: Option<char>,
+}
+
+/// A line of text split into grapheme clusters, each one knowing the amount
+/// of terminal columns it takes, so text can be addressed by grapheme index,
+/// byte offset or screen column.
+#[derive(Default, Clone)]
+pub struct Line {
+    string: String,
+    fragments: Vec<TextFragment>,
+}
+
+impl From<&str> for Line {
+    fn from(line_str: &str) -> Self {
+        Self::new(line_str, DEFAULT_TAB_WIDTH)
+    }
+}
+
+impl Line {
+    /// Splits a line whose tabs reach up to the next multiple of
+    /// `tab_width` columns.
+    pub fn new(line_str: &str, tab_width: usize) -> Self {
+        Self {
+            string: line_str.to_string(),
+            fragments: Self::build_fragments(line_str, tab_width.max(1)),
+        }
+    }
+
+    pub fn grapheme_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// Amount of columns taken by the graphemes before `grapheme_index`.
+    pub fn width_until(&self, grapheme_index: usize) -> usize {
+        self.fragments
+            .iter()
+            .take(grapheme_index)
+            .map(|fragment| fragment.width)
+            .sum()
+    }
+
+    /// Index of the grapheme drawn over `column`, or the grapheme count when
+    /// the column is past the end of the line.
+    pub fn grapheme_index_at_column(&self, column: usize) -> usize {
+        let mut current_column = 0;
+
+        for (grapheme_index, fragment) in self.fragments.iter().enumerate() {
+            current_column += fragment.width;
+            if current_column > column {
+                return grapheme_index;
+            }
+        }
+
+        self.grapheme_count()
+    }
+
+    pub fn byte_index(&self, grapheme_index: usize) -> usize {
+        self.fragments
+            .get(grapheme_index)
+            .map_or(self.string.len(), |fragment| fragment.start_byte)
+    }
+
+    /// Returns the text drawn on the given column range, replacing wide
+    /// graphemes cut by the range edges with an ellipsis and tabs with the
+    /// spaces of the columns they cover.
+    pub fn get_visible(&self, columns: Range<usize>) -> String {
+        let mut visible = String::new();
+        let mut current_column = 0;
+
+        for (grapheme_index, fragment) in self.fragments.iter().enumerate() {
+            let fragment_end = current_column + fragment.width;
+
+            if current_column >= columns.end {
+                break;
+            }
+
+            if fragment_end > columns.start {
+                if self.grapheme(grapheme_index) == "\t" {
+                    let visible_width = std::cmp::min(fragment_end, columns.end)
+                        - std::cmp::max(current_column, columns.start);
+                    visible.push_str(&" ".repeat(visible_width));
+                } else if current_column < columns.start || fragment_end > columns.end {
+                    visible.push('⋯');
+                } else if let Some(replacement) = fragment.replacement {
+                    visible.push(replacement);
+                } else {
+                    visible.push_str(self.grapheme(grapheme_index));
+                }
+            }
+
+            current_column = fragment_end;
+        }
+
+        visible
+    }
+
+    /// Grapheme ranges of the screen rows the line takes when wrapped to
+    /// `width` columns, with the rows after the first being
+    /// `continuation_width` columns wide. Rows break after the last blank
+    /// that fits, or inside words longer than a row.
+    pub fn wrap(&self, width: usize, continuation_width: usize) -> Vec<Range<usize>> {
+        let mut rows = Vec::new();
+        let mut row_start = 0;
+        let mut row_width = 0;
+        let mut available_width = width.max(1);
+        let mut last_break = None;
+        let mut grapheme_index = 0;
+
+        while let Some(fragment) = self.fragments.get(grapheme_index) {
+            if row_width + fragment.width > available_width && grapheme_index > row_start {
+                let row_end = last_break.unwrap_or(grapheme_index);
+                rows.push(row_start..row_end);
+                row_start = row_end;
+                row_width = 0;
+                available_width = continuation_width.max(1);
+                last_break = None;
+                grapheme_index = row_end;
+                continue;
+            }
+
+            row_width += fragment.width;
+            grapheme_index += 1;
+            if self
+                .grapheme(grapheme_index - 1)
+                .chars()
+                .all(char::is_whitespace)
+            {
+                last_break = Some(grapheme_index);
+            }
+        }
+
+        rows.push(row_start..self.grapheme_count());
+        rows
+    }
+
+    /// Grapheme ranges of the non-overlapping matches of `regex`, widened
+    /// to whole graphemes.
+    pub fn find_matches(&self, regex: &Regex) -> Vec<Range<usize>> {
+        regex
+            .find_iter(&self.string)
+            .map(|matched| {
+                let start = self.grapheme_index_at_byte(matched.start());
+                let end = if matched.is_empty() {
+                    start
+                } else {
+                    self.grapheme_index_at_byte(matched.end() - 1) + 1
+                };
+                start..end
+            })
+            .collect()
+    }
+
+    /// Index of the grapheme containing `byte_index`.
+    pub fn grapheme_index_at_byte(&self, byte_index: usize) -> usize {
+        if byte_index >= self.string.len() {
+            return self.grapheme_count();
+        }
+
+        self.fragments
+            .partition_point(|fragment| fragment.start_byte <= byte_index)
+            .saturating_sub(1)
+    }
+
+    pub fn grapheme(&self, grapheme_index: usize) -> &str {
+        &self.string[self.byte_index(grapheme_index)..self.byte_index(grapheme_index + 1)]
+    }
+
+    fn build_fragments(line_str: &str, tab_width: usize) -> Vec<TextFragment> {
+        let mut column = 0;
+
+        line_str
+            .grapheme_indices(true)
+            .map(|(start_byte, grapheme)| {
+                let (width, replacement) = if grapheme == "\t" {
+                    (tab_width - column % tab_width, None)
+                } else {
+                    Self::grapheme_display(grapheme)
+                };
+                column += width;
+                TextFragment {
+                    start_byte,
+                    width,
+                    replacement,
+                }
+            })
+            .collect()
+    }
+
+    /// Zero-width and control graphemes are drawn as a visible placeholder so
+    /// every grapheme owns at least one column for the caret to land on.
+    fn grapheme_display(grapheme: &str) -> (usize, Option<char>) {
+        match grapheme {
+            _ if grapheme.chars().all(char::is_control) => (1, Some('▯')),
+            _ => match grapheme.width() {
+                0 => (1, Some('·')),
+                width => (width, None),
+            },
+        }
     }
 }
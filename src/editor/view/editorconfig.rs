@@ -0,0 +1,391 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+use regex::Regex;
+
+const FILE_NAME: &str = ".editorconfig";
+
+/// A `[glob]` section of an `.editorconfig` file and its properties.
+struct Section {
+    glob: String,
+    properties: Vec<(String, String)>,
+}
+
+/// A parsed `.editorconfig` file, matching globs against paths relative to
+/// the directory it's in.
+struct ConfigFile {
+    directory: PathBuf,
+    root: bool,
+    sections: Vec<Section>,
+}
+
+/// Properties of the `.editorconfig` files above a file that apply to it,
+/// by lowercase name. Files closer to it and later sections win, and
+/// `unset` removes a property.
+pub fn properties(file_path: &Path) -> BTreeMap<String, String> {
+    let file_path = fs::canonicalize(file_path).unwrap_or_else(|_| absolute(file_path));
+    let mut config_files = Vec::new();
+
+    for directory in file_path.ancestors().skip(1) {
+        let config_path = directory.join(FILE_NAME);
+        let contents = match fs::read_to_string(&config_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                error!("Couldn't read {}: {err}", config_path.display());
+                continue;
+            }
+        };
+
+        let config_file = parse(&contents, directory);
+        let root = config_file.root;
+        config_files.push(config_file);
+        if root {
+            break;
+        }
+    }
+
+    let mut properties = BTreeMap::new();
+    for config_file in config_files.iter().rev() {
+        let Some(relative_path) = relative_path(&file_path, &config_file.directory) else {
+            continue;
+        };
+        for section in &config_file.sections {
+            if !glob_matches(&section.glob, &relative_path) {
+                continue;
+            }
+            for (name, value) in &section.properties {
+                if value == "unset" {
+                    properties.remove(name);
+                } else {
+                    properties.insert(name.clone(), value.clone());
+                }
+            }
+        }
+    }
+    properties
+}
+
+fn absolute(file_path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .map(|current_dir| current_dir.join(file_path))
+        .unwrap_or_else(|_| file_path.to_path_buf())
+}
+
+/// Path of the file below `directory`, with `/` between its components.
+fn relative_path(file_path: &Path, directory: &Path) -> Option<String> {
+    let relative_path = file_path.strip_prefix(directory).ok()?;
+    let components: Vec<_> = relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    Some(components.join("/"))
+}
+
+/// Reads the sections of a file, skipping the lines it can't understand.
+/// Names and the values of the known properties are case insensitive, so
+/// they're lowercased.
+fn parse(contents: &str, directory: &Path) -> ConfigFile {
+    let mut config_file = ConfigFile {
+        directory: directory.to_path_buf(),
+        root: false,
+        sections: Vec::new(),
+    };
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        if let Some(glob) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            config_file.sections.push(Section {
+                glob: glob.to_string(),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        let value = value.trim();
+        let value = if is_known_property(&name) {
+            value.to_lowercase()
+        } else {
+            value.to_string()
+        };
+
+        match config_file.sections.last_mut() {
+            Some(section) => section.properties.push((name, value)),
+            None if name == "root" => config_file.root = value == "true",
+            None => (),
+        }
+    }
+
+    config_file
+}
+
+fn is_known_property(name: &str) -> bool {
+    matches!(
+        name,
+        "root"
+            | "indent_style"
+            | "indent_size"
+            | "tab_width"
+            | "end_of_line"
+            | "charset"
+            | "trim_trailing_whitespace"
+            | "insert_final_newline"
+            | "max_line_length"
+    )
+}
+
+/// Matches a section glob against a path relative to the `.editorconfig`
+/// file. Globs without a `/` match the file name in any directory.
+fn glob_matches(glob: &str, relative_path: &str) -> bool {
+    let mut numeric_ranges = Vec::new();
+    let Some(pattern) = glob_pattern(glob, &mut numeric_ranges) else {
+        return false;
+    };
+
+    let pattern = if glob.contains('/') {
+        format!("^{}$", pattern.strip_prefix('/').unwrap_or(&pattern))
+    } else {
+        format!("^(?:.*/)?{pattern}$")
+    };
+    let Ok(regex) = Regex::new(&pattern) else {
+        return false;
+    };
+    let Some(captures) = regex.captures(relative_path) else {
+        return false;
+    };
+
+    numeric_ranges
+        .iter()
+        .enumerate()
+        .all(|(index, (start, end))| {
+            // A number in an alternative that didn't match has nothing to
+            // check.
+            match captures.get(index + 1) {
+                Some(number) => number
+                    .as_str()
+                    .parse::<i64>()
+                    .is_ok_and(|number| (*start..=*end).contains(&number)),
+                None => true,
+            }
+        })
+}
+
+/// Translates a glob into a regex, where `*` matches within a directory,
+/// `**` across directories, `?` one character, `[abc]` and `[!abc]` a set,
+/// `{a,b}` alternatives and `{1..5}` a number in a range. Numbers are
+/// captured in order, their ranges being pushed to `numeric_ranges` so the
+/// caller can check them.
+fn glob_pattern(glob: &str, numeric_ranges: &mut Vec<(i64, i64)>) -> Option<String> {
+    let mut pattern = String::new();
+    let mut chars = glob.chars().peekable();
+    // Whether each brace still open holds alternatives, or is a literal
+    // one like in `{single}`.
+    let mut open_braces: Vec<bool> = Vec::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    pattern.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                match rest.find(']') {
+                    Some(end) if end > 0 && !rest[..end].contains('/') => {
+                        let set = &rest[..end];
+                        let (negated, set) = match set.strip_prefix('!') {
+                            Some(set) => (true, set),
+                            None => (false, set),
+                        };
+                        pattern.push('[');
+                        if negated {
+                            pattern.push('^');
+                        }
+                        for c in set.chars() {
+                            if matches!(c, '[' | ']' | '\\' | '^' | '&' | '~') {
+                                pattern.push('\\');
+                            }
+                            pattern.push(c);
+                        }
+                        pattern.push(']');
+                        for _ in 0..=set.chars().count() + usize::from(negated) {
+                            chars.next();
+                        }
+                    }
+                    _ => pattern.push_str(r"\["),
+                }
+            }
+            '{' => {
+                let rest: String = chars.clone().collect();
+                let Some(end) = matching_brace(&rest) else {
+                    pattern.push_str(r"\{");
+                    continue;
+                };
+                let inner = &rest[..end];
+
+                if let Some(range) = numeric_range(inner) {
+                    numeric_ranges.push(range);
+                    pattern.push_str(r"([+-]?\d+)");
+                    for _ in 0..=inner.chars().count() {
+                        chars.next();
+                    }
+                } else if inner.contains(',') {
+                    pattern.push_str("(?:");
+                    open_braces.push(true);
+                } else {
+                    pattern.push_str(r"\{");
+                    open_braces.push(false);
+                }
+            }
+            ',' if open_braces.last() == Some(&true) => pattern.push('|'),
+            '}' => match open_braces.pop() {
+                Some(true) => pattern.push(')'),
+                _ => pattern.push_str(r"\}"),
+            },
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    open_braces.is_empty().then_some(pattern)
+}
+
+/// Byte index of the `}` closing a brace that was just opened.
+fn matching_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+fn numeric_range(text: &str) -> Option<(i64, i64)> {
+    let (start, end) = text.split_once("..")?;
+    let start = start.parse().ok()?;
+    let end = end.parse().ok()?;
+    Some((std::cmp::min(start, end), std::cmp::max(start, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    fn check(glob: &str, matching: &[&str], not_matching: &[&str]) {
+        for path in matching {
+            assert!(glob_matches(glob, path), "{glob} should match {path}");
+        }
+        for path in not_matching {
+            assert!(!glob_matches(glob, path), "{glob} shouldn't match {path}");
+        }
+    }
+
+    #[test]
+    fn star_matches_within_a_directory() {
+        check("*.c", &["a.c", "dir/a.c", ".c"], &["a.h", "a.cc"]);
+        check("a*e.c", &["ae.c", "abe.c", "abcde.c"], &["a/e.c", "ab/e.c"]);
+        check("a/*.c", &["a/b.c"], &["a/b/c.c", "x/a/b.c"]);
+    }
+
+    #[test]
+    fn double_star_matches_across_directories() {
+        check("a**z.c", &["az.c", "a/z.c", "a/b/c/z.c"], &["a/z.h"]);
+        check("a/**/z.c", &["a/b/z.c", "a/b/c/z.c"], &["b/a/c/z.c"]);
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        check("som?.c", &["some.c"], &["som.c", "someth.c", "som/.c"]);
+    }
+
+    #[test]
+    fn brackets_match_a_set() {
+        check("[ab].a", &["a.a", "b.a"], &["c.a", "ab.a"]);
+        check("[!ab].b", &["c.b"], &["a.b", "b.b"]);
+        check("[a-c].c", &["a.c", "b.c", "c.c"], &["d.c"]);
+        check("[^a].d", &["^.d", "a.d"], &["b.d"]);
+        // A slash ends a set, so the brackets are literal.
+        check("ab[e/]cd.i", &["ab[e/]cd.i"], &["abecd.i", "ab/cd.i"]);
+        check("[ab", &["[ab"], &["a"]);
+    }
+
+    #[test]
+    fn braces_match_alternatives() {
+        check(
+            "*.{py,js,html}",
+            &["a.py", "a.js", "a.html"],
+            &["a.css", "a.{py,js,html}"],
+        );
+        check("{single}.b", &["{single}.b"], &["single.b"]);
+        check("{}.c", &["{}.c"], &[".c"]);
+        check(
+            "{word,{also},this}.g",
+            &["word.g", "{also}.g", "this.g"],
+            &["also.g"],
+        );
+        check("{a,{b,c}}.f", &["a.f", "b.f", "c.f"], &["d.f"]);
+        check("{unclosed.e", &["{unclosed.e"], &["unclosed.e"]);
+    }
+
+    #[test]
+    fn braces_match_numbers_in_a_range() {
+        check("{3..120}", &["3", "15", "60", "120"], &["1", "5a", "121"]);
+        check(
+            "{-3..3}.txt",
+            &["-3.txt", "0.txt", "+2.txt"],
+            &["-4.txt", "4.txt"],
+        );
+        check("{5..1}", &["1", "5"], &["6"]);
+        // Each range checks its own number.
+        check("{1..3}-{10..20}", &["2-15"], &["2-25", "4-15"]);
+        check(
+            "{foo,bar}{1..3}.txt",
+            &["foo2.txt", "bar3.txt"],
+            &["bar4.txt"],
+        );
+        check("{a{1..2},b}.c", &["a1.c", "b.c"], &["a3.c"]);
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        check(r"\*.txt", &["*.txt"], &["a.txt"]);
+        check(r"\{a,b}.txt", &["{a,b}.txt"], &["a.txt"]);
+        check(r"a\?.c", &["a?.c"], &["ab.c"]);
+    }
+
+    #[test]
+    fn slash_anchors_to_the_directory() {
+        check("/a.txt", &["a.txt"], &["sub/a.txt"]);
+        check("sub/*.txt", &["sub/b.txt"], &["other/sub/b.txt", "b.txt"]);
+        check("a.txt", &["a.txt", "sub/a.txt"], &["ba.txt"]);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{collections::BTreeMap, io::Error};
+
+use crossterm::style::Color;
+use unicode_width::UnicodeWidthChar;
+
+use super::super::terminal;
+
+const LINE_NUMBER_COLOR: Color = Color::DarkGrey;
+const CURRENT_LINE_NUMBER_COLOR: Color = Color::Yellow;
+/// Columns taken by every sign column.
+const SIGN_WIDTH: usize = 2;
+/// Digits line numbers take at least, so the gutter doesn't resize while a
+/// small file grows.
+const MIN_NUMBER_DIGITS: usize = 3;
+
+/// How the gutter numbers lines, chosen by the `number` and
+/// `relativenumber` options.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineNumbers {
+    Hidden,
+    Absolute,
+    /// Distance from the caret line.
+    Relative,
+    /// Distance from the caret line, with the caret line numbered.
+    Hybrid,
+}
+
+impl LineNumbers {
+    pub fn new(number: bool, relative_number: bool) -> Self {
+        match (number, relative_number) {
+            (false, false) => LineNumbers::Hidden,
+            (true, false) => LineNumbers::Absolute,
+            (false, true) => LineNumbers::Relative,
+            (true, true) => LineNumbers::Hybrid,
+        }
+    }
+}
+
+/// A mark drawn in the gutter next to a line, like a diagnostic or a
+/// bookmark, cut to the two columns of its sign column.
+#[derive(Clone)]
+pub struct Sign {
+    pub text: String,
+    pub color: Color,
+}
+
+/// Signs of one feature by line index, drawn in their own column.
+struct SignColumn {
+    name: &'static str,
+    signs: BTreeMap<usize, Sign>,
+}
+
+/// Columns left of the text, holding the sign columns that have signs and
+/// then the line numbers.
+#[derive(Default)]
+pub struct Gutter {
+    sign_columns: Vec<SignColumn>,
+}
+
+impl Gutter {
+    /// Replaces the signs of the column called `name`, adding the column
+    /// after the others the first time it's used.
+    pub fn set_signs(&mut self, name: &'static str, signs: BTreeMap<usize, Sign>) {
+        match self
+            .sign_columns
+            .iter_mut()
+            .find(|column| column.name == name)
+        {
+            Some(column) => column.signs = signs,
+            None => self.sign_columns.push(SignColumn { name, signs }),
+        }
+    }
+
+    pub fn width(&self, line_numbers: LineNumbers, line_count: usize) -> usize {
+        self.visible_sign_columns().count() * SIGN_WIDTH
+            + Self::number_width(line_numbers, line_count)
+    }
+
+    /// Draws the gutter of a screen row showing `line_index`, or of a row a
+    /// wrapped line continues on when it's `None`.
+    pub fn draw(
+        &self,
+        line_numbers: LineNumbers,
+        line_count: usize,
+        line_index: Option<usize>,
+        caret_line: usize,
+    ) -> Result<(), Error> {
+        for column in self.visible_sign_columns() {
+            let sign = line_index.and_then(|line_index| column.signs.get(&line_index));
+            match sign {
+                Some(sign) => {
+                    let text = Self::fit_sign(&sign.text);
+                    terminal::print_styled(&text, Some(sign.color), None)?;
+                }
+                None => terminal::print_inline(&" ".repeat(SIGN_WIDTH))?,
+            }
+        }
+
+        let number_width = Self::number_width(line_numbers, line_count);
+        if number_width == 0 {
+            return Ok(());
+        }
+
+        let digits = number_width - 1;
+        let Some(line_index) = line_index else {
+            return terminal::print_inline(&" ".repeat(number_width));
+        };
+        let distance = line_index.abs_diff(caret_line);
+        let number = match line_numbers {
+            LineNumbers::Absolute => format!("{:>digits$} ", line_index + 1),
+            LineNumbers::Hybrid if distance == 0 => format!("{:<digits$} ", line_index + 1),
+            _ => format!("{distance:>digits$} "),
+        };
+        let color = if distance == 0 {
+            CURRENT_LINE_NUMBER_COLOR
+        } else {
+            LINE_NUMBER_COLOR
+        };
+
+        terminal::print_styled(&number, Some(color), None)
+    }
+
+    fn visible_sign_columns(&self) -> impl Iterator<Item = &SignColumn> {
+        self.sign_columns
+            .iter()
+            .filter(|column| !column.signs.is_empty())
+    }
+
+    /// Columns of the line numbers and the space after them.
+    fn number_width(line_numbers: LineNumbers, line_count: usize) -> usize {
+        if line_numbers == LineNumbers::Hidden {
+            return 0;
+        }
+
+        let digits = line_count.max(1).to_string().len();
+        std::cmp::max(digits, MIN_NUMBER_DIGITS) + 1
+    }
+
+    /// Cuts or pads a sign to the width of its column.
+    fn fit_sign(text: &str) -> String {
+        let mut fitted = String::new();
+        let mut width = 0;
+        for c in text.chars() {
+            let char_width = c.width().unwrap_or_default();
+            if width + char_width > SIGN_WIDTH {
+                break;
+            }
+            fitted.push(c);
+            width += char_width;
+        }
+
+        fitted + &" ".repeat(SIGN_WIDTH - width)
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    env, fs,
+    io::{BufReader, BufWriter, Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use super::super::terminal::Position;
+
+/// A single modification of the buffer text, addressed by char index.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub enum Edit {
+    Insert { char_index: usize, text: String },
+    Remove { char_index: usize, text: String },
+}
+
+impl Edit {
+    fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { char_index, text } => Edit::Remove {
+                char_index: *char_index,
+                text: text.clone(),
+            },
+            Edit::Remove { char_index, text } => Edit::Insert {
+                char_index: *char_index,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+/// Edits that are undone and redone as one step, with the caret location
+/// from before the first edit.
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+struct Change {
+    edits: Vec<Edit>,
+    caret: Position,
+}
+
+/// A text state in the undo tree, reached by applying `change` to the state
+/// of its parent.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+struct UndoState {
+    parent: usize,
+    change: Change,
+    redo_child: Option<usize>,
+}
+
+/// The edits to apply to the buffer when moving through the history and
+/// where the caret should end up afterwards.
+pub struct Revision {
+    pub edits: Vec<Edit>,
+    pub caret: Position,
+}
+
+/// Branching undo history. States are numbered in creation order, state 0
+/// being the text as it was loaded, so every undone change stays reachable
+/// by moving through the numbers.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct History {
+    states: Vec<UndoState>,
+    current: usize,
+    saved: usize,
+    pending: Option<Change>,
+}
+
+/// History stored on disk along with a hash of the text it ends on, so it
+/// is only reused when the file wasn't changed outside the editor.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct UndoFile {
+    content_hash: u64,
+    history: History,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            states: vec![UndoState {
+                parent: 0,
+                change: Change::default(),
+                redo_child: None,
+            }],
+            current: 0,
+            saved: 0,
+            pending: None,
+        }
+    }
+}
+
+impl History {
+    /// Reads the history stored for `file_path`, returning `None` when there
+    /// is none or it belongs to a different version of the file.
+    pub fn read(file_path: &Path, content_hash: u64) -> Result<Option<History>, Error> {
+        let undo_file_path = Self::undo_file_path(file_path)?;
+        let file = match fs::File::open(undo_file_path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let undo_file: UndoFile = serde_json::from_reader(BufReader::new(file))?;
+
+        if undo_file.content_hash != content_hash || !undo_file.history.is_consistent() {
+            return Ok(None);
+        }
+
+        let mut history = undo_file.history;
+        history.pending = None;
+        history.saved = history.current;
+        Ok(Some(history))
+    }
+
+    /// Stores the history for `file_path`, writing into a temporary file
+    /// renamed over the undo file so a failed write keeps the last one.
+    pub fn write(&self, file_path: &Path, content_hash: u64) -> Result<(), Error> {
+        let undo_file_path = Self::undo_file_path(file_path)?;
+        if let Some(undo_directory) = undo_file_path.parent() {
+            fs::create_dir_all(undo_directory)?;
+        }
+        let Some(file_name) = undo_file_path.file_name() else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Invalid undo file name",
+            ));
+        };
+        let temp_path =
+            undo_file_path.with_file_name(format!(".{}.hecto~", file_name.to_string_lossy()));
+
+        let write_result = (|| {
+            let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+            serde_json::to_writer(
+                &mut writer,
+                &UndoFile {
+                    content_hash,
+                    history: self.clone(),
+                },
+            )?;
+            writer.into_inner()?.sync_all()?;
+
+            fs::rename(&temp_path, &undo_file_path)
+        })();
+
+        if write_result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        write_result
+    }
+
+    /// Checks that every state index in a history read from disk points
+    /// inside the tree, as a broken file would otherwise panic when undoing.
+    fn is_consistent(&self) -> bool {
+        let state_count = self.states.len();
+
+        self.current < state_count
+            && self.states.iter().enumerate().all(|(index, state)| {
+                (index == 0 || state.parent < index)
+                    && state.redo_child.is_none_or(|child| child < state_count)
+            })
+    }
+
+    /// Undo files live in the data directory, named after the canonical path
+    /// of the file they belong to.
+    fn undo_file_path(file_path: &Path) -> Result<PathBuf, Error> {
+        let canonical_path = fs::canonicalize(file_path)?;
+        let file_name = canonical_path
+            .to_string_lossy()
+            .replace(['/', '\\', ':'], "%");
+
+        let data_directory = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+            .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No data directory"))?;
+
+        Ok(data_directory.join("hecto").join("undo").join(file_name))
+    }
+
+    pub fn current_state(&self) -> usize {
+        self.current
+    }
+
+    pub fn is_at_saved_state(&self) -> bool {
+        self.pending.is_none() && self.current == self.saved
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved = self.current;
+    }
+
+    /// Adds an edit to the change being built, starting a new one at `caret`
+    /// if there is none.
+    pub fn record(&mut self, edit: Edit, caret: Position) {
+        self.pending
+            .get_or_insert_with(|| Change {
+                edits: Vec::new(),
+                caret,
+            })
+            .edits
+            .push(edit);
+    }
+
+    /// Closes the change being built so the next edit starts a new undo step.
+    pub fn commit(&mut self) {
+        let Some(change) = self.pending.take() else {
+            return;
+        };
+
+        let new_state = self.states.len();
+        self.states.push(UndoState {
+            parent: self.current,
+            change,
+            redo_child: None,
+        });
+        self.states[self.current].redo_child = Some(new_state);
+        self.current = new_state;
+    }
+
+    pub fn undo(&mut self) -> Option<Revision> {
+        self.commit();
+
+        if self.current == 0 {
+            return None;
+        }
+
+        let undone_state = self.current;
+        self.current = self.states[undone_state].parent;
+        self.states[self.current].redo_child = Some(undone_state);
+
+        Some(Revision {
+            edits: self.undo_edits(undone_state),
+            caret: self.states[undone_state].change.caret,
+        })
+    }
+
+    pub fn redo(&mut self) -> Option<Revision> {
+        self.commit();
+
+        let redone_state = self.states[self.current].redo_child?;
+        self.current = redone_state;
+
+        Some(Revision {
+            edits: self.states[redone_state].change.edits.clone(),
+            caret: self.states[redone_state].change.caret,
+        })
+    }
+
+    /// Moves to the state created right before or after the current one,
+    /// walking across branches of the tree when needed.
+    pub fn travel(&mut self, newer: bool) -> Option<Revision> {
+        self.commit();
+
+        let target = if newer {
+            Some(self.current + 1).filter(|state| *state < self.states.len())
+        } else {
+            self.current.checked_sub(1)
+        }?;
+
+        let target_path = self.path_from_root(target);
+        let mut edits = Vec::new();
+        let mut caret = self.states[self.current].change.caret;
+
+        while !target_path.contains(&self.current) {
+            edits.extend(self.undo_edits(self.current));
+            caret = self.states[self.current].change.caret;
+            let parent = self.states[self.current].parent;
+            self.states[parent].redo_child = Some(self.current);
+            self.current = parent;
+        }
+
+        let common_ancestor = target_path
+            .iter()
+            .position(|state| *state == self.current)
+            .unwrap_or_default();
+
+        for state in target_path.iter().skip(common_ancestor + 1) {
+            edits.extend(self.states[*state].change.edits.iter().cloned());
+            caret = self.states[*state].change.caret;
+            self.states[self.current].redo_child = Some(*state);
+            self.current = *state;
+        }
+
+        Some(Revision { edits, caret })
+    }
+
+    fn undo_edits(&self, state: usize) -> Vec<Edit> {
+        self.states[state]
+            .change
+            .edits
+            .iter()
+            .rev()
+            .map(Edit::inverse)
+            .collect()
+    }
+
+    fn path_from_root(&self, state: usize) -> Vec<usize> {
+        let mut path = vec![state];
+        let mut current = state;
+
+        while current != 0 {
+            current = self.states[current].parent;
+            path.push(current);
+        }
+
+        path.reverse();
+        path
+    }
+}
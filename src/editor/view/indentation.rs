@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use super::buffer::Line;
+
+/// Lines looked at from the start of a file to guess how it's indented.
+const SAMPLE_LINES: usize = 1000;
+/// Widest space indentation level considered, wider steps being alignment.
+const MAX_INDENT_STEP: usize = 8;
+
+/// How a file indents its lines.
+#[derive(Clone, Copy, PartialEq)]
+pub enum IndentStyle {
+    Tabs,
+    /// A number of spaces per level.
+    Spaces(usize),
+}
+
+/// Indentation guessed from the lines of a file, which is `mixed` when
+/// some lines are indented with tabs and others with spaces.
+pub struct DetectedIndentation {
+    pub style: IndentStyle,
+    pub mixed: bool,
+}
+
+fn is_blank(grapheme: &str) -> bool {
+    grapheme == " " || grapheme == "\t"
+}
+
+/// Bytes of the spaces and tabs a line starts with.
+pub fn indent_length(text: &str) -> usize {
+    text.len() - text.trim_start_matches([' ', '\t']).len()
+}
+
+/// Columns of the spaces and tabs a line starts with.
+pub fn indent_width(text: &str, tab_width: usize) -> usize {
+    let indent = Line::new(&text[..indent_length(text)], tab_width);
+    indent.width_until(indent.grapheme_count())
+}
+
+/// Blanks reaching from column `start` to column `end`, made of tabs up to
+/// the last tab stop before `end` unless `expand_tab`.
+pub fn blanks(start: usize, end: usize, tab_width: usize, expand_tab: bool) -> String {
+    if expand_tab || end <= start {
+        return " ".repeat(end.saturating_sub(start));
+    }
+
+    let mut blanks = String::new();
+    let mut column = start;
+    while (column / tab_width + 1) * tab_width <= end {
+        blanks.push('\t');
+        column = (column / tab_width + 1) * tab_width;
+    }
+    blanks + &" ".repeat(end - column)
+}
+
+/// Rewrites the runs of blanks holding a tab for tab stops every
+/// `new_tab_width` columns, keeping the text in the columns it had with
+/// `tab_width`. With `include_spaces`, runs of several spaces are rewritten
+/// too.
+pub fn retab_line(
+    text: &str,
+    tab_width: usize,
+    new_tab_width: usize,
+    expand_tab: bool,
+    include_spaces: bool,
+) -> String {
+    let line = Line::new(text, tab_width);
+    let mut retabbed = String::new();
+    let mut run_start = 0;
+
+    // The index past the last grapheme ends the last run of blanks.
+    for grapheme_index in 0..=line.grapheme_count() {
+        let grapheme = line.grapheme(grapheme_index);
+        if is_blank(grapheme) {
+            continue;
+        }
+
+        if run_start < grapheme_index {
+            let run_bytes = line.byte_index(run_start)..line.byte_index(grapheme_index);
+            let run = &text[run_bytes];
+            if run.contains('\t') || (include_spaces && run.len() > 1) {
+                let start = line.width_until(run_start);
+                let end = line.width_until(grapheme_index);
+                retabbed.push_str(&blanks(start, end, new_tab_width, expand_tab));
+            } else {
+                retabbed.push_str(run);
+            }
+        }
+        retabbed.push_str(grapheme);
+        run_start = grapheme_index + 1;
+    }
+
+    retabbed
+}
+
+/// Indentation for lines coming after `previous_line`, going one
+/// `shift_width` deeper after a line leaving a bracket open and one
+/// shallower for lines starting by closing one. Blank lines get none.
+pub fn reindent(
+    previous_line: Option<&str>,
+    lines: &[String],
+    tab_width: usize,
+    shift_width: usize,
+    expand_tab: bool,
+) -> Vec<String> {
+    let shift_width = shift_width as isize;
+    let next_width = |text: &str, width: isize| {
+        let (leading_closers, balance) = bracket_balance(text);
+        std::cmp::max(width + (balance + leading_closers) * shift_width, 0)
+    };
+    let mut width = previous_line.map_or(0, |text| {
+        next_width(text, indent_width(text, tab_width) as isize)
+    });
+
+    lines
+        .iter()
+        .map(|text| {
+            if text.trim().is_empty() {
+                return String::new();
+            }
+            let (leading_closers, _) = bracket_balance(text);
+            let line_width = std::cmp::max(width - leading_closers * shift_width, 0);
+            width = next_width(text, line_width);
+            blanks(0, line_width as usize, tab_width, expand_tab)
+        })
+        .collect()
+}
+
+/// Closing brackets a line starts with, and how many more brackets it
+/// opens than it closes, leaving out the ones in strings and line comments.
+fn bracket_balance(text: &str) -> (isize, isize) {
+    let content = text.trim_start();
+    let leading_closers = content
+        .chars()
+        .take_while(|c| matches!(c, ')' | ']' | '}'))
+        .count();
+    let mut balance = 0;
+    let mut in_string = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            _ if in_string => (),
+            '/' if chars.peek() == Some(&'/') => break,
+            '(' | '[' | '{' => balance += 1,
+            ')' | ']' | '}' => balance -= 1,
+            _ => (),
+        }
+    }
+
+    (leading_closers as isize, balance)
+}
+
+/// Guesses the indentation of a file from its first lines, going with the
+/// style most lines use and with the most common step between the space
+/// indented lines. Returns `None` when no line is indented.
+pub fn detect(lines: impl Iterator<Item = String>) -> Option<DetectedIndentation> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    let mut steps: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut previous_width = Some(0);
+
+    for text in lines.take(SAMPLE_LINES) {
+        let indent_length = indent_length(&text);
+        let (indent, content) = text.split_at(indent_length);
+        // Blank lines and the `*` lines of block comments say nothing about
+        // the indentation.
+        if content.is_empty() || (!indent.contains('\t') && content.starts_with('*')) {
+            continue;
+        }
+
+        if indent.starts_with('\t') {
+            tab_lines += 1;
+            previous_width = None;
+            continue;
+        }
+        if indent.contains('\t') {
+            space_lines += 1;
+            previous_width = None;
+            continue;
+        }
+
+        let width = indent.len();
+        if width > 0 {
+            space_lines += 1;
+        }
+        if let Some(previous_width) = previous_width {
+            let step = width.saturating_sub(previous_width);
+            if (1..=MAX_INDENT_STEP).contains(&step) {
+                *steps.entry(step).or_default() += 1;
+            }
+        }
+        previous_width = Some(width);
+    }
+
+    let style = if tab_lines == 0 && space_lines == 0 {
+        return None;
+    } else if tab_lines >= space_lines {
+        IndentStyle::Tabs
+    } else {
+        // The last of the most common steps is kept, so going from the
+        // widest step lets ties go to the narrowest.
+        let (step, _) = steps.into_iter().rev().max_by_key(|(_, count)| *count)?;
+        IndentStyle::Spaces(step)
+    };
+
+    Some(DetectedIndentation {
+        style,
+        mixed: tab_lines > 0 && space_lines > 0,
+    })
+}
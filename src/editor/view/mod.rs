@@ -1,22 +1,87 @@
-use core::panic;
-use std::{fmt::Display, io::Error, thread::current};
+use std::{collections::BTreeMap, io::Error, ops::Range};
 
-use buffer::Buffer;
+use buffer::{Buffer, Line, LineEnding};
+use crossterm::style::Color;
+use gutter::{Gutter, LineNumbers, Sign};
+use indentation::IndentStyle;
 use log::error;
+use motion::MotionKind;
+use search::SearchQuery;
+use selection::{SelectedSpan, Selection};
+use settings::Settings;
+use substitution::Substitution;
+use syntax::{Highlighter, Syntaxes};
+use unicode_width::UnicodeWidthStr;
 
 use super::terminal::{self, MovementDirection};
 
+pub use motion::{CharSearch, Motion, MotionResult};
+pub use search::SearchDirection;
+pub use selection::{CaseChange, SelectedText, SelectionKind};
+pub use settings::MAX_TAB_STOP;
+pub use text_object::{ObjectKind, TextObject};
+
 mod buffer;
+mod editorconfig;
+mod gutter;
+mod history;
+mod indentation;
+mod motion;
+mod search;
+mod selection;
+mod settings;
+mod substitution;
+mod syntax;
+mod text_object;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const SEARCH_HIGHLIGHT: Color = Color::Yellow;
+const CURRENT_MATCH_HIGHLIGHT: Color = Color::Magenta;
+const SHOW_BREAK_COLOR: Color = Color::DarkGrey;
+const BOOKMARK_COLOR: Color = Color::Blue;
+const COLOR_COLUMN_COLOR: Color = Color::DarkGrey;
+const SELECTION_HIGHLIGHT: Color = Color::Grey;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+struct TextStyle {
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+/// Where the caret was when a search prompt was opened.
+struct SearchOrigin {
+    location: terminal::Position,
+    scroll_offset: terminal::Position,
+    first_row: usize,
+    highlighted_search: Option<SearchQuery>,
+}
 
 pub struct View {
     pub buffer: Buffer,
+    pub settings: Settings,
     pub needs_redraw: bool,
     pub scroll_offset: terminal::Position,
+    /// Wrapped row of the line at `scroll_offset.y` shown at the top of the
+    /// view, when lines wrap.
+    first_row: usize,
     pub location: terminal::Position,
     pub position: terminal::Position,
+    last_search: Option<SearchQuery>,
+    highlighted_search: Option<SearchQuery>,
+    search_origin: Option<SearchOrigin>,
+    substitution: Option<Substitution>,
+    syntaxes: Syntaxes,
+    highlighter: Highlighter,
+    gutter: Gutter,
+    /// Caret line the drawn relative line numbers count from.
+    numbered_line: usize,
+    /// Whether the file was found to indent lines with both tabs and spaces.
+    mixed_indentation: bool,
+    /// What's selected in visual mode, or for an operator to act on.
+    selection: Option<Selection>,
+    /// The last `f`, `t`, `F` or `T` search, which `;` and `,` repeat.
+    last_find: Option<CharSearch>,
     width: u16,
     height: u16,
 }
@@ -28,10 +93,23 @@ impl Default for View {
 
         View {
             buffer: Buffer::default(),
+            settings: Settings::default(),
             needs_redraw: true,
             scroll_offset: terminal::Position::default(),
+            first_row: 0,
             location: terminal::Position::default(),
             position: terminal::Position { x: 0, y: 0 },
+            last_search: None,
+            highlighted_search: None,
+            search_origin: None,
+            substitution: None,
+            syntaxes: Syntaxes::default(),
+            highlighter: Highlighter::default(),
+            gutter: Gutter::default(),
+            numbered_line: 0,
+            mixed_indentation: false,
+            selection: None,
+            last_find: None,
             width: terminal_width,
             height: terminal_height,
         }
@@ -39,44 +117,64 @@ impl Default for View {
 }
 
 impl View {
-    pub fn _render_wrapped(&mut self) -> Result<(), Error> {
+    pub fn render(&mut self) -> Result<(), Error> {
         terminal::clear_screen()?;
-        let mut row = 0;
-        let mut text_line = 0;
+        if let Some(line_index) = self.buffer.take_first_changed_line() {
+            self.highlighter.invalidate_from(line_index);
+        }
+        self.update_bookmark_signs();
+        self.numbered_line = self.location.y;
 
-        while row < self.height {
-            terminal::move_cursor_to(&terminal::Position {
-                x: 0,
-                y: row as usize,
-            })?;
-            if let Some(line) = self.buffer.contents.get(text_line as usize) {
-                text_line += 1;
-                row += self._draw_line_wrapped(&line.to_string(), row as usize)? as u16;
+        if self.settings.wrap {
+            self.render_wrapped()?;
+        } else {
+            for row in 0..self.height {
+                terminal::move_cursor_to(&terminal::Position {
+                    x: 0,
+                    y: row as usize,
+                })?;
+                let line_index = row as usize + self.scroll_offset.y;
+                if let Some(line) = self.line(line_index) {
+                    let styles = self.styles(&line, line_index);
+                    self.draw_line(&line, line_index, &styles)?;
+                }
             }
         }
-
         self.needs_redraw = false;
         terminal::execute_queue()
     }
 
-    pub fn render(&mut self) -> Result<(), Error> {
-        terminal::clear_screen()?;
+    /// Draws the lines from the top row of the view, every line taking as
+    /// many screen rows as it needs.
+    fn render_wrapped(&mut self) -> Result<(), Error> {
+        let mut line_index = self.scroll_offset.y;
+        let mut first_row = self.first_row;
+        let mut screen_row = 0;
 
-        for row in 0..self.height {
-            terminal::move_cursor_to(&terminal::Position {
-                x: 0,
-                y: row as usize,
-            })?;
-            if let Some(text) = self
-                .buffer
-                .contents
-                .get(row as usize + self.scroll_offset.y)
-            {
-                self.draw_line(&text.to_owned())?;
+        while screen_row < self.height as usize {
+            let Some(line) = self.line(line_index) else {
+                break;
+            };
+            let styles = self.styles(&line, line_index);
+            let rows = self.wrapped_rows(&line);
+
+            for row in first_row..rows.len() {
+                if screen_row >= self.height as usize {
+                    break;
+                }
+                terminal::move_cursor_to(&terminal::Position {
+                    x: 0,
+                    y: screen_row,
+                })?;
+                self.draw_wrapped_row(&line, line_index, &styles, &rows, row)?;
+                screen_row += 1;
             }
+
+            line_index += 1;
+            first_row = 0;
         }
-        self.needs_redraw = false;
-        terminal::execute_queue()
+
+        Ok(())
     }
 
     pub fn render_title_screen(&self) -> Result<(), Error> {
@@ -89,7 +187,7 @@ impl View {
             Self::draw_empty_line()?;
         }
 
-        Self::draw_title()?;
+        self.draw_title()?;
         terminal::execute_queue()
     }
 
@@ -97,9 +195,9 @@ impl View {
         terminal::print("~")
     }
 
-    fn draw_title() -> Result<(), Error> {
-        let title_y_position = (crossterm::terminal::size()?.1 / 3) - 2;
-        let title_x_position = (crossterm::terminal::size()?.0 / 2) - 2;
+    fn draw_title(&self) -> Result<(), Error> {
+        let title_y_position = (self.height / 3).saturating_sub(2);
+        let title_x_position = (self.width / 2).saturating_sub(2);
 
         terminal::move_cursor_to(&terminal::Position {
             x: title_x_position as usize,
@@ -116,77 +214,497 @@ impl View {
         terminal::print(VERSION)
     }
 
-    fn _draw_line_wrapped(&self, line: &str, row: usize) -> Result<usize, Error> {
-        let mut lines_used = 1;
-        let mut l_pointer = 0;
-        let mut r_pointer = self.width as usize;
+    /// Draws the visible part of a line, in runs of graphemes sharing the
+    /// same colors.
+    fn draw_line(&self, line: &Line, line_index: usize, styles: &[TextStyle]) -> Result<(), Error> {
+        let columns = self.scroll_offset.x..self.text_width() + self.scroll_offset.x;
 
-        while r_pointer < line.len() {
-            terminal::print(line[l_pointer..r_pointer].trim())?;
-            terminal::move_cursor_to(&terminal::Position { x: 0, y: row + 1 })?;
-            l_pointer += self.width as usize;
-            r_pointer += self.width as usize;
-            lines_used += 1;
-        }
+        terminal::clear_line()?;
+        self.draw_gutter(Some(line_index))?;
+        Self::draw_columns(line, styles, columns.clone())?;
+        self.draw_line_end(line, line_index, columns)?;
+        terminal::execute_queue()
+    }
+
+    /// Draws a row of a wrapped line, after the continuation marker on the
+    /// rows the line continues on.
+    fn draw_wrapped_row(
+        &self,
+        line: &Line,
+        line_index: usize,
+        styles: &[TextStyle],
+        rows: &[Range<usize>],
+        row: usize,
+    ) -> Result<(), Error> {
+        let columns = line.width_until(rows[row].start)..line.width_until(rows[row].end);
 
         terminal::clear_line()?;
-        terminal::print(line[l_pointer..line.len()].trim())?;
+        self.draw_gutter((row == 0).then_some(line_index))?;
+        if row > 0 && self.show_break_width() > 0 {
+            terminal::print_styled(&self.settings.show_break, Some(SHOW_BREAK_COLOR), None)?;
+        }
+        Self::draw_columns(line, styles, columns.clone())?;
+        if row + 1 == rows.len() {
+            let row_width = if row == 0 {
+                self.text_width()
+            } else {
+                self.text_width() - self.show_break_width()
+            };
+            self.draw_line_end(line, line_index, columns.start..columns.start + row_width)?;
+        }
+        terminal::execute_queue()
+    }
+
+    /// Draws what follows the end of a line on a screen row showing the
+    /// given columns of it: a cell for a selected line break, and the
+    /// `colorcolumn` marker when the line is shorter than it.
+    fn draw_line_end(
+        &self,
+        line: &Line,
+        line_index: usize,
+        columns: Range<usize>,
+    ) -> Result<(), Error> {
+        let line_width = line.width_until(line.grapheme_count());
+        if line_width < columns.start {
+            return Ok(());
+        }
+
+        let mut line_end = line_width;
+        let line_break_selected = self
+            .selected_span(line_index)
+            .is_some_and(|span| span.line_break);
+        if line_break_selected && line_end < columns.end {
+            terminal::print_styled(" ", Some(Color::Black), Some(SELECTION_HIGHLIGHT))?;
+            line_end += 1;
+        }
+
+        let Some(column) = self.settings.color_column.checked_sub(1) else {
+            return Ok(());
+        };
+        if column < line_end || column >= columns.end {
+            return Ok(());
+        }
 
-        Ok(lines_used)
+        terminal::print_inline(&" ".repeat(column - line_end))?;
+        terminal::print_styled(" ", None, Some(COLOR_COLUMN_COLOR))
     }
 
-    fn draw_line(&self, line: &str) -> Result<(), Error> {
-        let l_pointer = std::cmp::min(line.len(), self.scroll_offset.x);
-        let r_pointer = std::cmp::min(line.len(), self.width as usize + self.scroll_offset.x);
+    fn draw_gutter(&self, line_index: Option<usize>) -> Result<(), Error> {
+        if self.gutter_width() == 0 {
+            return Ok(());
+        }
+
+        self.gutter.draw(
+            self.line_numbers(),
+            self.buffer.line_count(),
+            line_index,
+            self.location.y,
+        )
+    }
 
-        terminal::print(&line[l_pointer..r_pointer])?;
-        terminal::execute_queue()
+    fn line_numbers(&self) -> LineNumbers {
+        LineNumbers::new(self.settings.number, self.settings.relative_number)
+    }
+
+    /// Columns of the gutter, which is left out when it wouldn't leave
+    /// room for any text.
+    fn gutter_width(&self) -> usize {
+        let width = self
+            .gutter
+            .width(self.line_numbers(), self.buffer.line_count());
+        if width < self.width as usize {
+            width
+        } else {
+            0
+        }
+    }
+
+    /// Columns left for the text next to the gutter.
+    fn text_width(&self) -> usize {
+        self.width as usize - self.gutter_width()
+    }
+
+    /// A line of the buffer laid out with the configured tab stops.
+    fn line(&self, line_index: usize) -> Option<Line> {
+        self.buffer
+            .line_text(line_index)
+            .map(|line| Line::new(&line, self.settings.tab_stop))
+    }
+
+    /// Shows the marks of the buffer in a sign column.
+    fn update_bookmark_signs(&mut self) {
+        let mut signs = BTreeMap::new();
+        for (name, at) in self.buffer.marks() {
+            if !name.is_ascii_lowercase() {
+                continue;
+            }
+            signs.entry(at.y).or_insert(Sign {
+                text: name.to_string(),
+                color: BOOKMARK_COLOR,
+            });
+        }
+        self.gutter.set_signs("bookmarks", signs);
+    }
+
+    /// Queues the text of a line on the given columns.
+    fn draw_columns(line: &Line, styles: &[TextStyle], columns: Range<usize>) -> Result<(), Error> {
+        let l_pointer = columns.start;
+        let r_pointer = columns.end;
+        let mut run_start = 0;
+        while run_start < styles.len() {
+            let style = styles[run_start];
+            let run_end = styles[run_start..]
+                .iter()
+                .position(|other| *other != style)
+                .map_or(styles.len(), |length| run_start + length);
+            let start = line.width_until(run_start).clamp(l_pointer, r_pointer);
+            let end = line.width_until(run_end).clamp(l_pointer, r_pointer);
+
+            if end > start {
+                let text = line.get_visible(start..end);
+                if style == TextStyle::default() {
+                    terminal::print_inline(&text)?;
+                } else {
+                    terminal::print_styled(&text, style.foreground, style.background)?;
+                }
+            }
+            run_start = run_end;
+        }
+
+        Ok(())
+    }
+
+    /// Colors of every grapheme of a line, from the syntax highlighting
+    /// overlaid with the search matches.
+    fn styles(&mut self, line: &Line, line_index: usize) -> Vec<TextStyle> {
+        let mut styles = vec![TextStyle::default(); line.grapheme_count()];
+
+        for token in self.highlighter.highlight_line(&self.buffer, line_index) {
+            let start = line.grapheme_index_at_byte(token.bytes.start);
+            let end = line.grapheme_index_at_byte(token.bytes.end);
+            for style in &mut styles[start..end] {
+                style.foreground = Some(token.kind.color());
+            }
+        }
+
+        if let Some(column) = self.settings.color_column.checked_sub(1) {
+            let grapheme_index = line.grapheme_index_at_column(column);
+            if let Some(style) = styles.get_mut(grapheme_index) {
+                style.background = Some(COLOR_COLUMN_COLOR);
+            }
+        }
+
+        for (matched, color) in self.highlights(line, line_index) {
+            for style in &mut styles[matched] {
+                *style = TextStyle {
+                    foreground: Some(Color::Black),
+                    background: Some(color),
+                };
+            }
+        }
+
+        if let Some(span) = self.selected_span(line_index) {
+            for style in &mut styles[span.graphemes] {
+                *style = TextStyle {
+                    foreground: Some(Color::Black),
+                    background: Some(SELECTION_HIGHLIGHT),
+                };
+            }
+        }
+
+        styles
     }
 
-    fn fill_buffer(&mut self, file_contents: &str) {
-        for line in file_contents.lines() {
-            self.buffer.contents.push(line.to_string());
+    /// Grapheme ranges of a line to highlight, sorted and not overlapping,
+    /// with the match a substitution is asking about standing out from the
+    /// search matches.
+    fn highlights(&self, line: &Line, line_index: usize) -> Vec<(Range<usize>, Color)> {
+        let mut highlights: Vec<(Range<usize>, Color)> = self
+            .highlighted_search
+            .as_ref()
+            .map_or(Vec::new(), |query| line.find_matches(&query.regex))
+            .into_iter()
+            .map(|matched| (matched, SEARCH_HIGHLIGHT))
+            .collect();
+
+        let current_match = self
+            .substitution
+            .as_ref()
+            .and_then(|substitution| substitution.current_match())
+            .filter(|current_match| current_match.line_index == line_index);
+        if let Some(current_match) = current_match {
+            let start = line.grapheme_index_at_byte(current_match.bytes.start);
+            let end = line.grapheme_index_at_byte(current_match.bytes.end);
+            highlights.retain(|(matched, _)| matched.end <= start || matched.start >= end);
+            highlights.push((start..end, CURRENT_MATCH_HIGHLIGHT));
+            highlights.sort_by_key(|(matched, _)| matched.start);
         }
+
+        highlights
     }
 
     pub fn update_terminal_size(&mut self, width: u16, height: u16) {
-        // TODO update location and position when making terminal smaller
         self.height = height;
         self.width = width;
+        self.scroll_location_into_view();
         self.needs_redraw = true;
     }
 
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
     pub fn load_file(&mut self, file_path: String) -> Result<(), Error> {
-        let file_contents = std::fs::read_to_string(file_path)?;
+        let file_path = std::path::PathBuf::from(file_path);
+
+        match Buffer::load(&file_path) {
+            Ok(buffer) => self.buffer = buffer,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.buffer = Buffer::default();
+                self.buffer.file_path = Some(file_path);
+            }
+            Err(err) => return Err(err),
+        }
+
+        if self.settings.undo_file {
+            self.buffer.read_history();
+        }
+
+        // What the last file set for itself doesn't carry over.
+        self.settings.reset_file_options();
+        self.detect_syntax();
+        self.detect_indentation();
+        self.apply_editorconfig();
+        self.update_bookmark_signs();
+        self.location = terminal::Position::default();
+        self.scroll_offset = terminal::Position::default();
+        self.first_row = 0;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    /// Loads the user's syntax files, returning the problems found in them.
+    pub fn load_syntax_definitions(&mut self) -> Vec<String> {
+        let (syntaxes, errors) = Syntaxes::load();
+        self.syntaxes = syntaxes;
+        self.detect_syntax();
+        self.needs_redraw = true;
+        errors
+    }
+
+    fn detect_syntax(&mut self) {
+        self.highlighter = Highlighter::new(self.syntaxes.definition_for(&self.buffer));
+    }
+
+    /// Sets the indentation options to the style the file uses, keeping
+    /// the ones given with `:set` when no line is indented.
+    fn detect_indentation(&mut self) {
+        let lines = (0..self.buffer.line_count())
+            .filter_map(|line_index| self.buffer.line_text(line_index));
+        let Some(detected) = indentation::detect(lines) else {
+            self.mixed_indentation = false;
+            return;
+        };
+
+        match detected.style {
+            IndentStyle::Tabs => {
+                self.settings.expand_tab = false;
+                self.settings.shift_width = 0;
+            }
+            IndentStyle::Spaces(width) => {
+                self.settings.expand_tab = true;
+                self.settings.shift_width = width;
+            }
+        }
+        self.mixed_indentation = detected.mixed;
+    }
+
+    /// Applies the `.editorconfig` properties for the file, which win over
+    /// the detected indentation.
+    fn apply_editorconfig(&mut self) {
+        let Some(file_path) = self.buffer.file_path.clone() else {
+            return;
+        };
+        let properties = editorconfig::properties(&file_path);
+        let property = |name: &str| properties.get(name).map(String::as_str);
+        let number = |name: &str| {
+            property(name)
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|number| (1..=MAX_TAB_STOP).contains(number))
+        };
+
+        match property("indent_style") {
+            Some("tab") => self.settings.expand_tab = false,
+            Some("space") => self.settings.expand_tab = true,
+            _ => (),
+        }
+        if property("indent_size") == Some("tab") {
+            self.settings.shift_width = 0;
+        } else if let Some(indent_size) = number("indent_size") {
+            self.settings.shift_width = indent_size;
+        }
+        if let Some(tab_width) = number("tab_width").or_else(|| number("indent_size")) {
+            self.settings.tab_stop = tab_width;
+        }
+
+        let write_fixes = &mut self.buffer.write_fixes;
+        match property("end_of_line") {
+            Some("lf") => {
+                self.buffer.line_ending = LineEnding::Unix;
+                write_fixes.consistent_line_endings = true;
+            }
+            Some("crlf") => {
+                self.buffer.line_ending = LineEnding::Dos;
+                write_fixes.consistent_line_endings = true;
+            }
+            Some(line_ending) => error!("Unsupported end_of_line in .editorconfig: {line_ending}"),
+            None => (),
+        }
+        match property("charset") {
+            Some("utf-8") => self.buffer.byte_order_mark = false,
+            Some("utf-8-bom") => self.buffer.byte_order_mark = true,
+            Some(charset) => error!("Unsupported charset in .editorconfig: {charset}"),
+            None => (),
+        }
+        write_fixes.trim_trailing_whitespace = property("trim_trailing_whitespace") == Some("true");
+        write_fixes.final_newline = match property("insert_final_newline") {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        };
+
+        if property("max_line_length") == Some("off") {
+            self.settings.color_column = 0;
+        } else if let Some(max_line_length) = number("max_line_length") {
+            self.settings.color_column = max_line_length + 1;
+        }
+    }
+
+    /// Describes how new indentation is made, like `spaces:4` or `tabs`,
+    /// noting when the file mixes tabs and spaces.
+    pub fn indentation(&self) -> String {
+        let style = if self.settings.expand_tab {
+            format!("spaces:{}", self.settings.effective_shift_width())
+        } else {
+            String::from("tabs")
+        };
+
+        if self.mixed_indentation {
+            format!("{style} (mixed)")
+        } else {
+            style
+        }
+    }
 
-        for line in file_contents.lines() {
-            self.fill_buffer(line);
+    /// Name of the language the buffer is highlighted as, falling back to
+    /// the file extension.
+    pub fn file_type(&self) -> String {
+        self.highlighter
+            .language_name()
+            .map_or_else(|| self.buffer.file_type(), str::to_string)
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        let result = self.buffer.save();
+        self.keep_location_in_buffer();
+        result?;
+        self.write_history();
+        Ok(())
+    }
+
+    pub fn save_as(&mut self, file_path: std::path::PathBuf) -> Result<(), Error> {
+        let result = self.buffer.save_as(file_path);
+        self.keep_location_in_buffer();
+        result?;
+        self.write_history();
+        self.detect_syntax();
+        Ok(())
+    }
+
+    /// Moves the caret back into the text after the write fixes shortened
+    /// it.
+    fn keep_location_in_buffer(&mut self) {
+        self.location.y =
+            std::cmp::min(self.location.y, self.buffer.line_count().saturating_sub(1));
+        self.location.x = std::cmp::min(self.location.x, self.buffer.line_length(self.location.y));
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    fn write_history(&self) {
+        if self.settings.undo_file {
+            self.buffer.write_history();
+        }
+    }
+
+    /// Moves the caret to the start of a 1-based line number, clamped to
+    /// the lines of the buffer.
+    pub fn jump_to_line(&mut self, line_number: usize) {
+        self.buffer.commit_change();
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        self.location.y = std::cmp::min(line_number.saturating_sub(1), last_line);
+        self.location.x = 0;
+        self.scroll_location_into_view();
+    }
+
+    /// Marks the caret location with a letter, shown as a bookmark in the
+    /// gutter.
+    pub fn set_mark(&mut self, name: char) -> Result<(), String> {
+        if !name.is_ascii_lowercase() {
+            return Err(format!("Invalid mark name: {name}"));
         }
 
+        self.buffer.set_mark(name, self.location);
+        self.update_bookmark_signs();
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    /// Moves the caret to a mark, or only to its line unless `exact`.
+    pub fn jump_to_mark(&mut self, name: char, exact: bool) -> Result<(), String> {
+        let mark = self
+            .buffer
+            .mark(name)
+            .ok_or_else(|| format!("Mark not set: '{name}"))?;
+
+        self.jump_to_line(mark.y + 1);
+        if exact {
+            self.location.x = std::cmp::min(mark.x, self.buffer.line_length(self.location.y));
+            self.scroll_location_into_view();
+        }
         Ok(())
     }
 
     pub fn scroll(&mut self, direction: MovementDirection, amount: usize) {
+        if self.settings.wrap {
+            self.scroll_rows(direction, amount);
+            return;
+        }
+
         match direction {
             MovementDirection::Left => {
-                if self.scroll_offset.x > 0 {
-                    self.scroll_offset.x -= amount;
-                }
+                self.scroll_offset.x = self.scroll_offset.x.saturating_sub(amount);
             }
             MovementDirection::Right => {
                 self.scroll_offset.x += amount;
             }
             MovementDirection::Up => {
-                if self.scroll_offset.y > 0 {
-                    self.scroll_offset.y -= amount;
-                }
+                self.scroll_offset.y = self.scroll_offset.y.saturating_sub(amount);
             }
             MovementDirection::Down => {
-                self.scroll_offset.y += amount;
+                let last_line = self.buffer.line_count().saturating_sub(1);
+                self.scroll_offset.y = std::cmp::min(self.scroll_offset.y + amount, last_line);
             }
             _ => (),
         }
 
+        self.move_location_into_view();
         self.needs_redraw = true;
     }
 
@@ -195,115 +713,1578 @@ impl View {
         direction: MovementDirection,
         amount: usize,
     ) -> Result<(), std::io::Error> {
+        let last_line = self.buffer.line_count().saturating_sub(1);
+
         match direction {
             MovementDirection::Left => {
-                if self.location.x > 0 {
-                    self.location.x -= amount;
-                }
-
-                if self.position.x > 0 {
-                    self.position.x -= amount;
-                } else {
-                    self.scroll(direction, amount)
-                }
+                self.location.x = self.location.x.saturating_sub(amount);
             }
             MovementDirection::Right => {
-                let current_line = match self.buffer.contents.get(self.location.y) {
-                    Some(line) => line,
-                    None => {
-                        error!(
-                            "The view position on text is invalid:\rLocation: {}",
-                            self.location
-                        );
-                        panic!("The position on text is invalid");
-                    }
-                };
-
-                if self.location.x < current_line.len() {
-                    self.location.x = std::cmp::min(self.location.x + amount, current_line.len());
-
-                    if self.position.x >= self.width as usize {
-                        self.scroll(direction, amount);
-                    } else {
-                        self.position.x += amount;
-                    }
-                }
+                let line_length = self.buffer.line_length(self.location.y);
+                self.location.x = std::cmp::min(self.location.x + amount, line_length);
             }
             MovementDirection::Up => {
-                if self.location.y > 0 {
-                    self.location.y -= amount;
-                }
-
-                if self.position.y > 0 {
-                    self.position.y -= amount;
-                } else {
-                    self.scroll(direction, amount)
-                }
+                self.move_to_line(self.location.y.saturating_sub(amount));
             }
             MovementDirection::Down => {
-                if self.location.y <= self.buffer.contents.len() {
-                    self.location.y += amount;
-
-                    if self.position.y >= self.height as usize {
-                        self.scroll(direction, amount);
-                    } else {
-                        self.position.y += amount;
-                    }
-                }
+                self.move_to_line(std::cmp::min(self.location.y + amount, last_line));
             }
             MovementDirection::Top => {
-                if self.location.y == 0 || self.position.y == 0 {
-                    return Ok(());
-                }
-                self.location.y -= self.position.y - 1;
-                self.position.y = 0;
+                self.move_to_line(self.scroll_offset.y);
             }
             MovementDirection::Bottom => {
-                if self.location.y >= self.buffer.contents.len() - 1
-                    || self.position.y >= self.height as usize
-                {
-                    return Ok(());
-                }
-
-                let movement_force =
-                    std::cmp::min(self.height as usize, self.buffer.contents.len());
-                let movement_dif = movement_force as usize - self.position.y - 1;
-                self.location.y += movement_dif;
-                self.position.y = movement_force as usize;
+                self.move_to_line(self.last_visible_row().0);
             }
             MovementDirection::FullRight => {
-                let current_line = match self.buffer.contents.get(self.location.y) {
-                    Some(line) => line,
-                    None => {
-                        error!(
-                            "The view position on text is invalid:\rLocation: {}",
-                            self.location
-                        );
-                        panic!("The position on text is invalid");
-                    }
-                };
-
-                let location_distance_till_end = current_line.len() - self.location.x;
-                self.location.x = current_line.len();
-
-                if current_line.len() > self.width as usize {
-                    let caret_distance_till_end = self.width as usize - self.position.x;
-                    self.position.x = self.width as usize;
-                    self.scroll(
-                        MovementDirection::Right,
-                        location_distance_till_end - caret_distance_till_end,
-                    );
-                } else {
-                    self.position.x = current_line.len();
-                }
+                self.location.x = self.buffer.line_length(self.location.y);
             }
             MovementDirection::FullLeft => {
-                self.position.x = 0;
                 self.location.x = 0;
-                self.scroll(MovementDirection::Left, self.scroll_offset.x);
             }
         }
 
+        self.scroll_location_into_view();
+        terminal::move_cursor_to(&self.position)
+    }
+
+    /// Moves the caret by screen rows, which differ from lines only when
+    /// long lines wrap.
+    pub fn move_caret_by_rows(
+        &mut self,
+        direction: MovementDirection,
+        amount: usize,
+    ) -> Result<(), std::io::Error> {
+        if !self.settings.wrap {
+            return self.move_caret(direction, amount);
+        }
+
+        let (row, column) = self.wrapped_location();
+        let mut row = (self.location.y, row);
+        for _ in 0..amount {
+            let next_row = match direction {
+                MovementDirection::Up => self.previous_row(row),
+                _ => self.next_row(row),
+            };
+            let Some(next_row) = next_row else {
+                break;
+            };
+            row = next_row;
+        }
+
+        self.move_to_row(row, column);
+        self.scroll_location_into_view();
         terminal::move_cursor_to(&self.position)
     }
+
+    /// Moves the location to another line keeping the caret on the same
+    /// screen column as far as the new line allows it.
+    fn move_to_line(&mut self, line_index: usize) {
+        let column = self.location_column();
+        self.location.y = line_index;
+        self.location.x = self
+            .line(line_index)
+            .map_or(0, |line| line.grapheme_index_at_column(column));
+    }
+
+    fn location_column(&self) -> usize {
+        self.line(self.location.y)
+            .map_or(0, |line| line.width_until(self.location.x))
+    }
+
+    pub fn insert_char(&mut self, character: char) {
+        let previous_length = self.buffer.line_length(self.location.y);
+        self.buffer.insert_char(character, &self.location);
+        let inserted_graphemes = self.buffer.line_length(self.location.y) - previous_length;
+
+        self.location.x += inserted_graphemes;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Inserts text holding no line breaks at the caret, moving past it.
+    fn insert_text(&mut self, text: &str) {
+        let previous_length = self.buffer.line_length(self.location.y);
+        self.buffer.insert(text, &self.location);
+        let inserted_graphemes = self.buffer.line_length(self.location.y) - previous_length;
+
+        self.location.x += inserted_graphemes;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    pub fn insert_newline(&mut self) {
+        self.buffer.insert_newline(&self.location);
+        self.location.y += 1;
+        self.location.x = 0;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    pub fn delete(&mut self) {
+        self.buffer.delete(&self.location);
+        self.needs_redraw = true;
+    }
+
+    pub fn delete_backward(&mut self) {
+        if self.location.x > 0 {
+            self.location.x -= 1;
+        } else if self.location.y > 0 {
+            self.location.y -= 1;
+            self.location.x = self.buffer.line_length(self.location.y);
+        } else {
+            return;
+        }
+
+        self.buffer.delete(&self.location);
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Inserts a tab, or with `expandtab` the spaces up to the next tab
+    /// stop.
+    pub fn insert_tab(&mut self) {
+        if !self.settings.expand_tab {
+            self.insert_char('\t');
+            return;
+        }
+
+        let tab_stop = self.settings.tab_stop;
+        let column = self.location_column();
+        self.insert_text(&" ".repeat(tab_stop - column % tab_stop));
+    }
+
+    /// Indents the lines by one `shiftwidth`, or outdents them with
+    /// `outdent`, leaving empty lines alone. The caret moves to the first
+    /// non-blank of its line.
+    pub fn shift_lines(&mut self, lines: Range<usize>, outdent: bool) {
+        self.buffer.commit_change();
+        let tab_stop = self.settings.tab_stop;
+        let shift_width = self.settings.effective_shift_width();
+
+        for line_index in lines {
+            let Some(text) = self.buffer.line_text(line_index) else {
+                break;
+            };
+            if text.is_empty() {
+                continue;
+            }
+
+            let width = indentation::indent_width(&text, tab_stop);
+            let new_width = if outdent {
+                width.saturating_sub(shift_width)
+            } else {
+                width + shift_width
+            };
+            let indent = indentation::blanks(0, new_width, tab_stop, self.settings.expand_tab);
+            let indent_length = indentation::indent_length(&text);
+            if text[..indent_length] != indent {
+                self.buffer.replace(line_index, 0..indent_length, &indent);
+            }
+        }
+
+        self.buffer.commit_change();
+        self.move_to_first_non_blank();
+        self.needs_redraw = true;
+    }
+
+    /// Rewrites the blanks of the lines for a new tab stop, which becomes
+    /// the `tabstop` option, keeping the text in the same columns.
+    pub fn retab(&mut self, lines: Range<usize>, new_tab_stop: usize, include_spaces: bool) {
+        self.buffer.commit_change();
+
+        for line_index in lines {
+            let Some(text) = self.buffer.line_text(line_index) else {
+                break;
+            };
+            let retabbed = indentation::retab_line(
+                &text,
+                self.settings.tab_stop,
+                new_tab_stop,
+                self.settings.expand_tab,
+                include_spaces,
+            );
+            if retabbed != text {
+                self.buffer.replace(line_index, 0..text.len(), &retabbed);
+            }
+        }
+
+        self.buffer.commit_change();
+        self.settings.tab_stop = new_tab_stop;
+        self.location.x = std::cmp::min(self.location.x, self.buffer.line_length(self.location.y));
+        self.update_layout();
+    }
+
+    fn move_to_first_non_blank(&mut self) {
+        self.location.x = self.first_non_blank(self.location.y);
+        self.scroll_location_into_view();
+    }
+
+    fn first_non_blank(&self, line_index: usize) -> usize {
+        let text = self.buffer.line_text(line_index).unwrap_or_default();
+        let indent_length = indentation::indent_length(&text);
+        self.line(line_index)
+            .map_or(0, |line| line.grapheme_index_at_byte(indent_length))
+    }
+
+    /// Moves the caret as a motion does, `count` times. With
+    /// `operator_pending`, word motions stop at the end of the line.
+    pub fn apply_motion(
+        &mut self,
+        motion: Motion,
+        count: Option<usize>,
+        operator_pending: bool,
+    ) -> MotionResult {
+        let amount = count.unwrap_or(1);
+        let line_count = self.buffer.line_count();
+        let last_line = line_count.saturating_sub(1);
+
+        match motion {
+            Motion::Left if self.location.x == 0 => return Err(None),
+            Motion::Left => self.location.x = self.location.x.saturating_sub(amount),
+            Motion::Right => {
+                let line_length = self.buffer.line_length(self.location.y);
+                if self.location.x >= line_length {
+                    return Err(None);
+                }
+                self.location.x = std::cmp::min(self.location.x + amount, line_length);
+            }
+            Motion::Up if self.location.y == 0 => return Err(None),
+            Motion::Up => self.move_to_line(self.location.y.saturating_sub(amount)),
+            Motion::Down if self.location.y >= last_line => return Err(None),
+            Motion::Down => self.move_to_line(std::cmp::min(self.location.y + amount, last_line)),
+            Motion::RowUp | Motion::RowDown => {
+                let direction = if motion == Motion::RowUp {
+                    MovementDirection::Up
+                } else {
+                    MovementDirection::Down
+                };
+                self.move_caret_by_rows(direction, amount)
+                    .map_err(|err| Some(err.to_string()))?;
+            }
+            Motion::WordForward { big } => {
+                self.location = motion::word_forward(
+                    &self.buffer,
+                    self.location,
+                    amount,
+                    big,
+                    operator_pending,
+                );
+            }
+            Motion::WordBackward { big } => {
+                self.location = motion::word_backward(&self.buffer, self.location, amount, big);
+            }
+            Motion::WordEnd { big, stay } => {
+                self.location = motion::word_end(&self.buffer, self.location, amount, big, stay);
+            }
+            Motion::LineStart => self.location.x = 0,
+            Motion::FirstNonBlank => self.move_to_first_non_blank(),
+            Motion::LineEnd => {
+                self.location.y = std::cmp::min(self.location.y + amount - 1, last_line);
+                self.location.x = self.buffer.line_length(self.location.y).saturating_sub(1);
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let default_line = if motion == Motion::FirstLine {
+                    1
+                } else {
+                    line_count
+                };
+                self.jump_to_line(count.unwrap_or(default_line));
+                self.move_to_first_non_blank();
+            }
+            Motion::FindChar(search) => {
+                self.last_find = Some(search);
+                self.find_char(search, amount, false)?;
+            }
+            Motion::RepeatFind { reverse } => {
+                let last_find = self.last_find.ok_or(None)?;
+                let search = CharSearch {
+                    forward: last_find.forward != reverse,
+                    ..last_find
+                };
+                self.find_char(search, amount, true)?;
+            }
+            Motion::ParagraphForward | Motion::ParagraphBackward => {
+                let forward = motion == Motion::ParagraphForward;
+                self.location = motion::paragraph(&self.buffer, self.location.y, amount, forward);
+            }
+            Motion::Mark { name, exact } => self.jump_to_mark(name, exact).map_err(Some)?,
+            Motion::SearchNext { reverse } => {
+                let mut notice = None;
+                for _ in 0..amount {
+                    notice = self.search_next(reverse).map_err(Some)?;
+                }
+                return Ok(notice);
+            }
+        }
+
+        self.scroll_location_into_view();
+        Ok(None)
+    }
+
+    fn find_char(&mut self, search: CharSearch, count: usize, repeating: bool) -> MotionResult {
+        let line = self.line(self.location.y).ok_or(None)?;
+        self.location.x =
+            motion::find_char(&line, self.location.x, search, count, repeating).ok_or(None)?;
+        Ok(None)
+    }
+
+    /// Selects the text from the caret to where a motion goes for an
+    /// operator to act on, following how vim treats the motion kinds. An
+    /// exclusive motion ending at the start of a line ends at the end of
+    /// the line before, and takes whole lines when it started in the
+    /// indentation. With `for_change`, `w` stops at the end of a word as
+    /// `cw` does.
+    pub fn select_motion(
+        &mut self,
+        motion: Motion,
+        count: Option<usize>,
+        for_change: bool,
+    ) -> MotionResult {
+        let start = self.location;
+        let motion = match motion {
+            Motion::WordForward { big }
+                if for_change && !motion::is_on_blank(&self.buffer, start) =>
+            {
+                Motion::WordEnd { big, stay: true }
+            }
+            motion => motion,
+        };
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        let mut kind = motion.kind(self.last_find);
+
+        let notice = match self.apply_motion(motion, count, true) {
+            Ok(notice) => notice,
+            Err(err) => {
+                self.location = start;
+                return Err(err);
+            }
+        };
+        let (from, to) = selection::ordered(start, self.location);
+        // Going past the last paragraph takes the last grapheme.
+        if motion == Motion::ParagraphForward
+            && to.y == last_line
+            && self.buffer.line_length(to.y) > 0
+        {
+            kind = MotionKind::Inclusive;
+        }
+
+        let region = match kind {
+            MotionKind::Linewise => Some((SelectionKind::Linewise, to)),
+            MotionKind::Inclusive if to.x < self.buffer.line_length(to.y) => {
+                Some((SelectionKind::Characterwise, to))
+            }
+            MotionKind::Inclusive => self
+                .previous_grapheme(terminal::Position {
+                    x: self.buffer.line_length(to.y),
+                    y: to.y,
+                })
+                .map(|end| (SelectionKind::Characterwise, end)),
+            MotionKind::Exclusive if to.x == 0 && to.y > from.y => {
+                if from.x <= self.first_non_blank(from.y) {
+                    let end = terminal::Position { x: 0, y: to.y - 1 };
+                    Some((SelectionKind::Linewise, end))
+                } else {
+                    self.previous_grapheme(terminal::Position {
+                        x: self.buffer.line_length(to.y - 1),
+                        y: to.y - 1,
+                    })
+                    .map(|end| (SelectionKind::Characterwise, end))
+                }
+            }
+            MotionKind::Exclusive => self
+                .previous_grapheme(to)
+                .map(|end| (SelectionKind::Characterwise, end)),
+        };
+
+        match region {
+            Some((kind, end)) if (from.y, from.x) <= (end.y, end.x) => {
+                self.selection = Some(Selection {
+                    kind,
+                    anchor: from,
+                    visual: false,
+                });
+                self.location = end;
+                Ok(notice)
+            }
+            _ => {
+                self.location = start;
+                Err(None)
+            }
+        }
+    }
+
+    /// Selects `count` lines from the caret line for an operator to act on.
+    pub fn select_lines(&mut self, count: usize) -> bool {
+        let line_count = self.buffer.line_count();
+        if line_count == 0 {
+            return false;
+        }
+
+        self.selection = Some(Selection {
+            kind: SelectionKind::Linewise,
+            anchor: self.location,
+            visual: false,
+        });
+        self.location.y = std::cmp::min(self.location.y + count.max(1) - 1, line_count - 1);
+        true
+    }
+
+    /// Selects a text object at the caret for an operator to act on, or
+    /// grows the visual selection with it. Returns whether anything was
+    /// selected: an object with nothing inside, like `i(` in `()`, only
+    /// moves the caret where its text would go, and no object at all is
+    /// `None`.
+    pub fn select_object(&mut self, object: TextObject, count: Option<usize>) -> Option<bool> {
+        let (mut from, mut to) = match self.selection {
+            Some(selection) if selection.visual => {
+                selection::ordered(selection.anchor, self.location)
+            }
+            _ => (self.location, self.location),
+        };
+        if self.selection_kind() == Some(SelectionKind::Linewise) && from != to {
+            from.x = 0;
+            to.x = self.buffer.line_length(to.y);
+        }
+
+        let range = text_object::find(&self.buffer, object, from, to, count.unwrap_or(1))?;
+        let Some(end) = range.end else {
+            self.location = range.start;
+            self.scroll_location_into_view();
+            return Some(false);
+        };
+
+        let kind = if range.linewise {
+            SelectionKind::Linewise
+        } else {
+            SelectionKind::Characterwise
+        };
+        match &mut self.selection {
+            Some(selection) if selection.visual => {
+                selection.anchor = range.start;
+                selection.kind = kind;
+            }
+            _ => {
+                self.selection = Some(Selection {
+                    kind,
+                    anchor: range.start,
+                    visual: false,
+                });
+            }
+        }
+        self.location = end;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+        Some(true)
+    }
+
+    /// The grapheme before a location, being the line break of the line
+    /// before at the start of a line.
+    fn previous_grapheme(&self, at: terminal::Position) -> Option<terminal::Position> {
+        if at.x > 0 {
+            Some(terminal::Position {
+                x: at.x - 1,
+                y: at.y,
+            })
+        } else if at.y > 0 {
+            Some(terminal::Position {
+                x: self.buffer.line_length(at.y - 1),
+                y: at.y - 1,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Starts selecting from the caret in visual mode.
+    pub fn start_selection(&mut self, kind: SelectionKind) {
+        self.buffer.commit_change();
+        self.selection = Some(Selection {
+            kind,
+            anchor: self.location,
+            visual: true,
+        });
+        self.needs_redraw = true;
+    }
+
+    pub fn set_selection_kind(&mut self, kind: SelectionKind) {
+        if let Some(selection) = &mut self.selection {
+            selection.kind = kind;
+        }
+        self.needs_redraw = true;
+    }
+
+    /// Leaves visual mode, remembering the ends of the selection in the
+    /// `'<` and `'>` marks.
+    pub fn end_selection(&mut self) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+        if !selection.visual {
+            return;
+        }
+
+        let (mut start, mut end) = selection::ordered(selection.anchor, self.location);
+        if selection.kind == SelectionKind::Linewise {
+            start.x = 0;
+            end.x = self.buffer.line_length(end.y);
+        }
+        self.buffer.set_mark('<', start);
+        self.buffer.set_mark('>', end);
+        self.needs_redraw = true;
+    }
+
+    /// The first and last selected locations.
+    pub fn selection_ends(&self) -> Option<(terminal::Position, terminal::Position)> {
+        let selection = self.selection?;
+        Some(selection::ordered(selection.anchor, self.location))
+    }
+
+    pub fn selection_kind(&self) -> Option<SelectionKind> {
+        self.selection.map(|selection| selection.kind)
+    }
+
+    /// Moves the caret to the other end of the selection.
+    pub fn swap_selection_ends(&mut self) {
+        if let Some(selection) = &mut self.selection {
+            std::mem::swap(&mut selection.anchor, &mut self.location);
+        }
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Size of the selection shown in the status bar, in graphemes when it
+    /// is within a line and in lines otherwise.
+    pub fn selection_size(&self) -> Option<usize> {
+        let selection = self.selection?;
+        let (start, end) = selection::ordered(selection.anchor, self.location);
+
+        if selection.kind == SelectionKind::Characterwise && start.y == end.y {
+            Some(end.x - start.x + 1)
+        } else {
+            Some(end.y - start.y + 1)
+        }
+    }
+
+    /// Deletes the selection and leaves visual mode, returning what was
+    /// deleted.
+    pub fn delete_selection(&mut self) -> Option<SelectedText> {
+        let deleted = self.remove_selection(false)?;
+        self.buffer.commit_change();
+        Some(deleted)
+    }
+
+    /// Deletes the selection to type over it, keeping an empty line in place
+    /// of selected lines, and returns what was deleted. The change stays
+    /// open so what's typed next is undone with it.
+    pub fn change_selection(&mut self) -> Option<SelectedText> {
+        self.remove_selection(true)
+    }
+
+    /// Copies the selection and leaves visual mode with the caret at its
+    /// start, keeping its column for lines.
+    pub fn yank_selection(&mut self) -> Option<SelectedText> {
+        let selection = self.selection?;
+        let yanked = self.selected_text()?;
+        self.location = match selection.kind {
+            SelectionKind::Linewise => selection::ordered(selection.anchor, self.location).0,
+            _ => self.selection_start(),
+        };
+        self.end_selection();
+        self.scroll_location_into_view();
+        Some(yanked)
+    }
+
+    /// Shifts the selected lines by one `shiftwidth` and leaves visual mode.
+    pub fn shift_selection(&mut self, outdent: bool) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let (start, end) = selection::ordered(selection.anchor, self.location);
+
+        self.end_selection();
+        self.location = start;
+        self.shift_lines(start.y..end.y + 1, outdent);
+    }
+
+    /// Changes the case of the selected text and leaves visual mode.
+    pub fn change_selection_case(&mut self, case_change: CaseChange) {
+        let spans = self.selected_spans();
+        let start = self.selection_start();
+        self.end_selection();
+
+        for span in spans {
+            let Some(line) = self.line(span.line_index) else {
+                continue;
+            };
+            let bytes = line.byte_index(span.graphemes.start)..line.byte_index(span.graphemes.end);
+            let text = self.buffer.line_text(span.line_index).unwrap_or_default();
+            let changed = case_change.apply(&text[bytes.clone()]);
+            if changed != text[bytes.clone()] {
+                self.buffer.replace(span.line_index, bytes, &changed);
+            }
+        }
+
+        self.buffer.commit_change();
+        self.location = start;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Reindents the selected lines by their brackets and leaves visual
+    /// mode, with the caret on the first non-blank of the first line.
+    pub fn reindent_selection(&mut self) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let (start, end) = selection::ordered(selection.anchor, self.location);
+        self.end_selection();
+        self.buffer.commit_change();
+
+        let previous_line = (0..start.y)
+            .rev()
+            .filter_map(|line_index| self.buffer.line_text(line_index))
+            .find(|text| !text.trim().is_empty());
+        let lines: Vec<String> = (start.y..=end.y)
+            .filter_map(|line_index| self.buffer.line_text(line_index))
+            .collect();
+        let indents = indentation::reindent(
+            previous_line.as_deref(),
+            &lines,
+            self.settings.tab_stop,
+            self.settings.effective_shift_width(),
+            self.settings.expand_tab,
+        );
+
+        for (offset, (text, indent)) in lines.iter().zip(indents).enumerate() {
+            let indent_length = indentation::indent_length(text);
+            if text[..indent_length] != indent {
+                self.buffer
+                    .replace(start.y + offset, 0..indent_length, &indent);
+            }
+        }
+
+        self.buffer.commit_change();
+        self.location.y = start.y;
+        self.move_to_first_non_blank();
+        self.needs_redraw = true;
+    }
+
+    /// Replaces the selection with text and leaves visual mode, returning
+    /// what was selected. Lines put over characters go on lines of their
+    /// own.
+    pub fn put_over_selection(&mut self, text: &SelectedText) -> Option<SelectedText> {
+        let selection = self.selection?;
+        let (start, end) = selection::ordered(selection.anchor, self.location);
+        let reached_last_line = end.y + 1 >= self.buffer.line_count();
+        let removed = self.remove_selection(false)?;
+
+        match (selection.kind, text.kind) {
+            (SelectionKind::Linewise, _) => {
+                // Once the last lines are gone, the lines go after the ones
+                // left above them.
+                let after = reached_last_line && start.y > 0;
+                self.put_lines(&text.text, !after);
+            }
+            (_, SelectionKind::Linewise) => {
+                self.buffer.insert("\n", &self.location);
+                self.location = terminal::Position {
+                    x: 0,
+                    y: self.location.y + 1,
+                };
+                self.put_lines(&text.text, true);
+            }
+            (_, SelectionKind::Characterwise) => self.put_characters(&text.text, true),
+            (_, SelectionKind::Blockwise) => self.put_block(&text.text, true, 1),
+        }
+
+        self.buffer.commit_change();
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+        Some(removed)
+    }
+
+    /// Puts text after the caret, or before it with `before`, in the shape
+    /// it was taken: characters go next to the caret, lines below or above
+    /// its line and a block into the columns next to it.
+    pub fn put(&mut self, text: &SelectedText, before: bool, count: usize) {
+        self.buffer.commit_change();
+        match text.kind {
+            SelectionKind::Characterwise => self.put_characters(&text.text.repeat(count), before),
+            SelectionKind::Linewise => {
+                let mut lines = text.text.clone();
+                if !lines.ends_with('\n') {
+                    lines.push('\n');
+                }
+                self.put_lines(&lines.repeat(count), before);
+            }
+            SelectionKind::Blockwise => self.put_block(&text.text, before, count),
+        }
+        self.buffer.commit_change();
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Inserts characters next to the caret, leaving it on the last one put
+    /// when they fit on a line and on the first one otherwise.
+    fn put_characters(&mut self, text: &str, before: bool) {
+        let line_length = self.buffer.line_length(self.location.y);
+        let x = if before || line_length == 0 {
+            self.location.x
+        } else {
+            self.location.x + 1
+        };
+        let at = terminal::Position {
+            x: std::cmp::min(x, line_length),
+            y: self.location.y,
+        };
+
+        self.buffer.insert(text, &at);
+        self.location = at;
+        if !text.contains('\n') {
+            self.location.x += Line::from(text).grapheme_count().saturating_sub(1);
+        }
+    }
+
+    /// Inserts lines below or above the caret line, moving the caret to the
+    /// first non-blank of the first one.
+    fn put_lines(&mut self, text: &str, before: bool) {
+        let mut text = text.to_string();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        let line_count = self.buffer.line_count();
+        let first_line = if before {
+            self.location.y
+        } else {
+            self.location.y + 1
+        };
+
+        if first_line < line_count || line_count == 0 {
+            self.buffer.insert(
+                &text,
+                &terminal::Position {
+                    x: 0,
+                    y: first_line.min(line_count),
+                },
+            );
+        } else {
+            // The last line may not end with a line break to put lines
+            // after, so one is put before them instead.
+            text.pop();
+            self.buffer.insert(
+                &format!("\n{text}"),
+                &terminal::Position {
+                    x: self.buffer.line_length(self.location.y),
+                    y: self.location.y,
+                },
+            );
+        }
+
+        self.location.y = std::cmp::min(first_line, self.buffer.line_count().saturating_sub(1));
+        self.move_to_first_non_blank();
+    }
+
+    /// Inserts the lines of a block at the same column of the caret line
+    /// and the lines below it, adding lines at the end of the buffer and
+    /// padding short lines with spaces to reach the column. Every block line
+    /// is put `count` times side by side.
+    fn put_block(&mut self, text: &str, before: bool, count: usize) {
+        let tab_stop = self.settings.tab_stop;
+        let first_line = self.location.y;
+        let line = self.line(first_line).unwrap_or_default();
+        let column = if before || line.grapheme_count() == 0 {
+            line.width_until(self.location.x)
+        } else {
+            line.width_until(self.location.x + 1)
+        };
+        let block_lines: Vec<Line> = text
+            .split('\n')
+            .map(|block_line| Line::new(block_line, tab_stop))
+            .collect();
+        let block_width = block_lines
+            .iter()
+            .map(|block_line| block_line.width_until(block_line.grapheme_count()))
+            .max()
+            .unwrap_or(0);
+
+        for (offset, (text, block_line)) in text.split('\n').zip(&block_lines).enumerate() {
+            let line_index = first_line + offset;
+            let line_count = self.buffer.line_count();
+            if line_index >= line_count && line_count > 0 {
+                self.buffer.insert(
+                    "\n",
+                    &terminal::Position {
+                        x: self.buffer.line_length(line_count - 1),
+                        y: line_count - 1,
+                    },
+                );
+            }
+
+            let text_width = block_line.width_until(block_line.grapheme_count());
+            let copy = format!("{text}{}", " ".repeat(block_width - text_width));
+            let text = format!("{}{text}", copy.repeat(count.saturating_sub(1)));
+
+            let line = self.line(line_index).unwrap_or_default();
+            let line_width = line.width_until(line.grapheme_count());
+            let (x, padded) = if line_width < column {
+                let padding = " ".repeat(column - line_width);
+                (line.grapheme_count(), padding + &text)
+            } else {
+                let x = line.grapheme_index_at_column(column);
+                // Text after the block stays in one column.
+                let padding = if x < line.grapheme_count() {
+                    " ".repeat(block_width - text_width)
+                } else {
+                    String::new()
+                };
+                (x, format!("{text}{padding}"))
+            };
+            self.buffer
+                .insert(&padded, &terminal::Position { x, y: line_index });
+        }
+
+        self.location.x = self
+            .line(first_line)
+            .map_or(0, |line| line.grapheme_index_at_column(column));
+    }
+
+    /// Locations around whole lines to remove them with, taking the line
+    /// break before them when they end the buffer. With `keep_line`, only
+    /// their text is inside.
+    fn line_bounds(
+        &self,
+        first_line: usize,
+        last_line: usize,
+        keep_line: bool,
+    ) -> (terminal::Position, terminal::Position) {
+        let end_of_line = |line_index: usize| terminal::Position {
+            x: self.buffer.line_length(line_index),
+            y: line_index,
+        };
+        let line_start = |line_index: usize| terminal::Position {
+            x: 0,
+            y: line_index,
+        };
+
+        if keep_line {
+            (line_start(first_line), end_of_line(last_line))
+        } else if last_line + 1 < self.buffer.line_count() {
+            (line_start(first_line), line_start(last_line + 1))
+        } else if first_line > 0 {
+            (end_of_line(first_line - 1), end_of_line(last_line))
+        } else {
+            (line_start(0), end_of_line(last_line))
+        }
+    }
+
+    /// Removes the selected text and leaves visual mode, with the caret
+    /// where the selection started. With `keep_line`, selected lines are
+    /// emptied instead of removed.
+    fn remove_selection(&mut self, keep_line: bool) -> Option<SelectedText> {
+        let selection = self.selection?;
+        let removed = self.selected_text()?;
+        let spans = self.selected_spans();
+        let start = self.selection_start();
+        let (_, end) = selection::ordered(selection.anchor, self.location);
+        self.end_selection();
+
+        let line_count = self.buffer.line_count();
+        let end_of_line = |line_index: usize| terminal::Position {
+            x: self.buffer.line_length(line_index),
+            y: line_index,
+        };
+        let line_start = |line_index: usize| terminal::Position {
+            x: 0,
+            y: line_index,
+        };
+
+        match selection.kind {
+            SelectionKind::Characterwise => {
+                let to = if end.x < self.buffer.line_length(end.y) {
+                    terminal::Position {
+                        x: end.x + 1,
+                        y: end.y,
+                    }
+                } else if end.y + 1 < line_count {
+                    line_start(end.y + 1)
+                } else {
+                    end_of_line(end.y)
+                };
+                self.buffer.remove_between(&start, &to);
+            }
+            SelectionKind::Linewise => {
+                let (from, to) = self.line_bounds(start.y, end.y, keep_line);
+                self.buffer.remove_between(&from, &to);
+            }
+            SelectionKind::Blockwise => {
+                for span in spans.iter().rev() {
+                    self.buffer.remove_between(
+                        &terminal::Position {
+                            x: span.graphemes.start,
+                            y: span.line_index,
+                        },
+                        &terminal::Position {
+                            x: span.graphemes.end,
+                            y: span.line_index,
+                        },
+                    );
+                }
+            }
+        }
+
+        let last_line = self.buffer.line_count().saturating_sub(1);
+        self.location.y = std::cmp::min(start.y, last_line);
+        if selection.kind == SelectionKind::Linewise && !keep_line {
+            self.move_to_first_non_blank();
+        } else {
+            self.location.x = std::cmp::min(start.x, self.buffer.line_length(self.location.y));
+            self.scroll_location_into_view();
+        }
+        self.needs_redraw = true;
+        Some(removed)
+    }
+
+    /// The first selected location, which for a block is its top left
+    /// corner.
+    fn selection_start(&self) -> terminal::Position {
+        let Some(selection) = self.selection else {
+            return self.location;
+        };
+        let (start, _) = selection::ordered(selection.anchor, self.location);
+
+        match selection.kind {
+            SelectionKind::Characterwise => start,
+            SelectionKind::Linewise => terminal::Position { x: 0, y: start.y },
+            SelectionKind::Blockwise => terminal::Position {
+                x: self
+                    .selected_span(start.y)
+                    .map_or(0, |span| span.graphemes.start),
+                y: start.y,
+            },
+        }
+    }
+
+    /// Selected text with a `\n` after every selected line break, and after
+    /// every line of linewise and blockwise selections but the last block
+    /// line.
+    fn selected_text(&self) -> Option<SelectedText> {
+        let selection = self.selection?;
+        let spans = self.selected_spans();
+        let mut text = String::new();
+
+        for (index, span) in spans.iter().enumerate() {
+            let line_text = self.buffer.line_text(span.line_index).unwrap_or_default();
+            let line = Line::new(&line_text, self.settings.tab_stop);
+            let bytes = line.byte_index(span.graphemes.start)..line.byte_index(span.graphemes.end);
+            text.push_str(&line_text[bytes]);
+
+            let line_break = match selection.kind {
+                SelectionKind::Characterwise => span.line_break,
+                SelectionKind::Linewise => true,
+                SelectionKind::Blockwise => index + 1 < spans.len(),
+            };
+            if line_break {
+                text.push('\n');
+            }
+        }
+
+        Some(SelectedText {
+            text,
+            kind: selection.kind,
+        })
+    }
+
+    fn selected_spans(&self) -> Vec<SelectedSpan> {
+        let Some(selection) = self.selection else {
+            return Vec::new();
+        };
+        let (start, end) = selection::ordered(selection.anchor, self.location);
+
+        (start.y..=end.y)
+            .filter_map(|line_index| self.selected_span(line_index))
+            .collect()
+    }
+
+    /// The part of a line inside the selection.
+    fn selected_span(&self, line_index: usize) -> Option<SelectedSpan> {
+        let selection = self.selection?;
+        let (start, end) = selection::ordered(selection.anchor, self.location);
+        if line_index < start.y || line_index > end.y {
+            return None;
+        }
+
+        let line_length = self.buffer.line_length(line_index);
+        let has_line_break = line_index + 1 < self.buffer.line_count();
+        let span = match selection.kind {
+            SelectionKind::Characterwise => {
+                let first = if line_index == start.y { start.x } else { 0 };
+                let last = if line_index == end.y {
+                    end.x
+                } else {
+                    line_length
+                };
+                SelectedSpan {
+                    line_index,
+                    graphemes: first..std::cmp::min(last + 1, line_length),
+                    line_break: last >= line_length && has_line_break,
+                }
+            }
+            SelectionKind::Linewise => SelectedSpan {
+                line_index,
+                graphemes: 0..line_length,
+                line_break: has_line_break,
+            },
+            SelectionKind::Blockwise => {
+                let columns = self.block_columns(selection.anchor);
+                let line = self.line(line_index).unwrap_or_default();
+                let first = line.grapheme_index_at_column(columns.start);
+                let last = line.grapheme_index_at_column(columns.end - 1);
+                SelectedSpan {
+                    line_index,
+                    graphemes: first..std::cmp::min(last + 1, line_length),
+                    line_break: false,
+                }
+            }
+        };
+        Some(span)
+    }
+
+    /// Screen columns of a block selection, covering the graphemes under
+    /// both of its ends.
+    fn block_columns(&self, anchor: terminal::Position) -> Range<usize> {
+        let columns = |at: terminal::Position| {
+            let line = self.line(at.y).unwrap_or_default();
+            let start = line.width_until(at.x);
+            start..std::cmp::max(line.width_until(at.x + 1), start + 1)
+        };
+        let anchor_columns = columns(anchor);
+        let caret_columns = columns(self.location);
+
+        std::cmp::min(anchor_columns.start, caret_columns.start)
+            ..std::cmp::max(anchor_columns.end, caret_columns.end)
+    }
+
+    /// Remembers where the caret is when a search prompt opens, so the
+    /// incremental search can move it and cancelling puts it back.
+    pub fn start_search(&mut self) {
+        self.buffer.commit_change();
+        self.search_origin = Some(SearchOrigin {
+            location: self.location,
+            scroll_offset: self.scroll_offset,
+            first_row: self.first_row,
+            highlighted_search: self.highlighted_search.clone(),
+        });
+    }
+
+    /// Moves the caret to the first match of the pattern typed so far and
+    /// highlights all its matches.
+    pub fn preview_search(&mut self, pattern: &str, direction: SearchDirection) {
+        let Some(origin) = &self.search_origin else {
+            return;
+        };
+        let query = SearchQuery::new(pattern, direction)
+            .ok()
+            .filter(|query| !query.pattern.is_empty());
+
+        self.location = origin.location;
+        self.scroll_offset = origin.scroll_offset;
+        self.first_row = origin.first_row;
+        if let Some(result) = query
+            .as_ref()
+            .and_then(|query| self.buffer.find(query, &origin.location))
+        {
+            self.location = result.location;
+        }
+
+        self.highlighted_search = query;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    pub fn cancel_search(&mut self) {
+        let Some(origin) = self.search_origin.take() else {
+            return;
+        };
+
+        self.location = origin.location;
+        self.scroll_offset = origin.scroll_offset;
+        self.first_row = origin.first_row;
+        self.highlighted_search = origin.highlighted_search;
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Searches from where the prompt was opened, reusing the last pattern
+    /// when nothing was typed. Returns the notice to show when the search
+    /// wrapped around.
+    pub fn finish_search(
+        &mut self,
+        pattern: &str,
+        direction: SearchDirection,
+    ) -> Result<Option<String>, String> {
+        if let Some(origin) = self.search_origin.take() {
+            self.location = origin.location;
+            self.scroll_offset = origin.scroll_offset;
+            self.first_row = origin.first_row;
+        }
+
+        let pattern = self.pattern_or_last_search(pattern)?;
+        let query = SearchQuery::new(&pattern, direction)?;
+
+        self.last_search = Some(query.clone());
+        self.search(&query)
+    }
+
+    /// Repeats the last search, in the opposite direction when `reverse`.
+    pub fn search_next(&mut self, reverse: bool) -> Result<Option<String>, String> {
+        let mut query = self
+            .last_search
+            .clone()
+            .ok_or_else(|| String::from("No previous search pattern"))?;
+        if reverse {
+            query.direction = query.direction.reversed();
+        }
+
+        self.search(&query)
+    }
+
+    pub fn last_search_pattern(&self) -> Option<&str> {
+        self.last_search
+            .as_ref()
+            .map(|last_search| last_search.pattern.as_str())
+    }
+
+    /// The empty pattern stands for the last searched one, like in vim.
+    fn pattern_or_last_search(&self, pattern: &str) -> Result<String, String> {
+        if !pattern.is_empty() {
+            return Ok(pattern.to_string());
+        }
+
+        self.last_search
+            .as_ref()
+            .map(|last_search| last_search.pattern.clone())
+            .ok_or_else(|| String::from("No previous search pattern"))
+    }
+
+    pub fn clear_search_highlight(&mut self) {
+        self.highlighted_search = None;
+        self.needs_redraw = true;
+    }
+
+    fn search(&mut self, query: &SearchQuery) -> Result<Option<String>, String> {
+        self.buffer.commit_change();
+        self.highlighted_search = Some(query.clone());
+        self.needs_redraw = true;
+
+        let result = self.buffer.find(query, &self.location);
+        if let Some(result) = &result {
+            self.location = result.location;
+        }
+        self.scroll_location_into_view();
+
+        result
+            .map(|result| result.notice(query.direction))
+            .ok_or_else(|| format!("Pattern not found: {}", query.pattern))
+    }
+
+    /// Starts a `:substitute` over the given lines and moves the caret to
+    /// its first match, failing when the pattern doesn't match in them.
+    pub fn start_substitution(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+        global: bool,
+        ignore_case: bool,
+        lines: Range<usize>,
+    ) -> Result<(), String> {
+        let pattern = self.pattern_or_last_search(pattern)?;
+        let query = SearchQuery::new(&pattern, SearchDirection::Forward)?;
+        let regex = if ignore_case {
+            search::build_regex(&pattern, true)?
+        } else {
+            query.regex.clone()
+        };
+
+        self.last_search = Some(query.clone());
+        self.highlighted_search = Some(query);
+        self.needs_redraw = true;
+        self.buffer.commit_change();
+
+        let mut substitution = Substitution::new(regex, replacement, global, lines);
+        if !substitution.find_next(&self.buffer) {
+            return Err(format!("Pattern not found: {pattern}"));
+        }
+
+        self.substitution = Some(substitution);
+        self.move_to_current_match();
+        Ok(())
+    }
+
+    /// Replaces the match the substitution is on and moves to the next one,
+    /// returning whether there is one.
+    pub fn replace_current_match(&mut self) -> bool {
+        let Some(substitution) = &mut self.substitution else {
+            return false;
+        };
+
+        substitution.replace(&mut self.buffer);
+        let found = substitution.find_next(&self.buffer);
+        self.move_to_current_match();
+        found
+    }
+
+    /// Moves the substitution to its next match without replacing the
+    /// current one, returning whether there is one.
+    pub fn skip_current_match(&mut self) -> bool {
+        let Some(substitution) = &mut self.substitution else {
+            return false;
+        };
+
+        substitution.skip();
+        let found = substitution.find_next(&self.buffer);
+        self.move_to_current_match();
+        found
+    }
+
+    pub fn substitution_prompt(&self) -> Option<String> {
+        self.substitution.as_ref().map(Substitution::prompt)
+    }
+
+    pub fn replace_remaining_matches(&mut self) {
+        while self.replace_current_match() {}
+    }
+
+    /// Ends the substitution as one undo step with the caret on the last
+    /// changed line, returning a report of what was replaced.
+    pub fn finish_substitution(&mut self) -> Option<String> {
+        let substitution = self.substitution.take()?;
+        self.buffer.commit_change();
+        self.needs_redraw = true;
+
+        let last_changed_line = substitution.last_changed_line()?;
+        self.location = terminal::Position {
+            x: 0,
+            y: last_changed_line,
+        };
+        self.scroll_location_into_view();
+
+        let substitution_count = substitution.substitution_count();
+        let line_count = substitution.changed_line_count();
+        Some(format!(
+            "{substitution_count} substitution{} on {line_count} line{}",
+            if substitution_count == 1 { "" } else { "s" },
+            if line_count == 1 { "" } else { "s" },
+        ))
+    }
+
+    fn move_to_current_match(&mut self) {
+        let current_match = self
+            .substitution
+            .as_ref()
+            .and_then(|substitution| substitution.current_match());
+
+        if let Some(current_match) = current_match {
+            let x = self.line(current_match.line_index).map_or(0, |line| {
+                line.grapheme_index_at_byte(current_match.bytes.start)
+            });
+            self.location = terminal::Position {
+                x,
+                y: current_match.line_index,
+            };
+            self.scroll_location_into_view();
+        }
+
+        self.needs_redraw = true;
+    }
+
+    pub fn commit_change(&mut self) {
+        self.buffer.commit_change();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let caret = self.buffer.undo();
+        self.restore_caret(caret)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let caret = self.buffer.redo();
+        self.restore_caret(caret)
+    }
+
+    pub fn travel_history(&mut self, newer: bool) -> bool {
+        let caret = self.buffer.travel_history(newer);
+        self.restore_caret(caret)
+    }
+
+    fn restore_caret(&mut self, caret: Option<terminal::Position>) -> bool {
+        let Some(caret) = caret else {
+            return false;
+        };
+
+        self.location.y = std::cmp::min(caret.y, self.buffer.line_count().saturating_sub(1));
+        self.location.x = std::cmp::min(caret.x, self.buffer.line_length(self.location.y));
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+        true
+    }
+
+    /// Lays the view out again after an option changed how text is drawn.
+    pub fn update_layout(&mut self) {
+        if self.settings.wrap {
+            self.scroll_offset.x = 0;
+        } else {
+            self.first_row = 0;
+        }
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Adjusts the scroll offset so the text location is on screen and
+    /// recalculates the caret position from it.
+    fn scroll_location_into_view(&mut self) {
+        if self.settings.wrap {
+            self.scroll_wrapped_location_into_view();
+            return;
+        }
+
+        let width = self.text_width();
+        let height = self.height as usize;
+        let column = self.location_column();
+        let previous_offset = (self.scroll_offset.x, self.scroll_offset.y);
+
+        if column < self.scroll_offset.x {
+            self.scroll_offset.x = column;
+        } else if column >= self.scroll_offset.x + width {
+            self.scroll_offset.x = (column + 1).saturating_sub(width);
+        }
+
+        if self.location.y < self.scroll_offset.y {
+            self.scroll_offset.y = self.location.y;
+        } else if self.location.y >= self.scroll_offset.y + height {
+            self.scroll_offset.y = (self.location.y + 1).saturating_sub(height);
+        }
+
+        if previous_offset != (self.scroll_offset.x, self.scroll_offset.y) {
+            self.needs_redraw = true;
+        }
+
+        self.position.x = self.gutter_width() + column.saturating_sub(self.scroll_offset.x);
+        self.position.y = self.location.y.saturating_sub(self.scroll_offset.y);
+        self.redraw_relative_line_numbers();
+    }
+
+    /// Scrolls by screen rows so the row holding the text location is on
+    /// screen, even inside lines taller than the view.
+    fn scroll_wrapped_location_into_view(&mut self) {
+        let (row, column) = self.wrapped_location();
+        let previous_offset = (self.scroll_offset, self.first_row);
+
+        self.scroll_offset.x = 0;
+        self.first_row = std::cmp::min(
+            self.first_row,
+            self.row_count(self.scroll_offset.y).saturating_sub(1),
+        );
+
+        if (self.location.y, row) < (self.scroll_offset.y, self.first_row) {
+            self.scroll_offset.y = self.location.y;
+            self.first_row = row;
+        } else if self.screen_row((self.location.y, row)).is_none() {
+            let mut top_row = (self.location.y, row);
+            for _ in 1..self.height {
+                let Some(previous_row) = self.previous_row(top_row) else {
+                    break;
+                };
+                top_row = previous_row;
+            }
+            (self.scroll_offset.y, self.first_row) = top_row;
+        }
+
+        if previous_offset != (self.scroll_offset, self.first_row) {
+            self.needs_redraw = true;
+        }
+
+        self.position.x = self.gutter_width() + column;
+        self.position.y = self.screen_row((self.location.y, row)).unwrap_or_default();
+        self.redraw_relative_line_numbers();
+    }
+
+    /// Scrolls the wrapped lines by screen rows without moving the caret
+    /// unless it would leave the view.
+    fn scroll_rows(&mut self, direction: MovementDirection, amount: usize) {
+        let mut top_row = (self.scroll_offset.y, self.first_row);
+        for _ in 0..amount {
+            let next_row = match direction {
+                MovementDirection::Up => self.previous_row(top_row),
+                MovementDirection::Down => self.next_row(top_row),
+                _ => None,
+            };
+            let Some(next_row) = next_row else {
+                break;
+            };
+            top_row = next_row;
+        }
+        (self.scroll_offset.y, self.first_row) = top_row;
+
+        let (row, column) = self.wrapped_location();
+        let location_row = (self.location.y, row);
+        if location_row < top_row {
+            self.move_to_row(top_row, column);
+        } else if self.screen_row(location_row).is_none() {
+            self.move_to_row(self.last_visible_row(), column);
+        }
+
+        self.scroll_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Grapheme ranges of the screen rows a line takes when wrapped.
+    fn wrapped_rows(&self, line: &Line) -> Vec<Range<usize>> {
+        let width = self.text_width();
+        line.wrap(width, width - self.show_break_width())
+    }
+
+    fn row_count(&self, line_index: usize) -> usize {
+        self.line(line_index)
+            .map_or(1, |line| self.wrapped_rows(&line).len())
+    }
+
+    /// Columns taken by the continuation marker, which is left out when it
+    /// wouldn't leave room for any text.
+    fn show_break_width(&self) -> usize {
+        let width = self.settings.show_break.width();
+        if width < self.text_width() {
+            width
+        } else {
+            0
+        }
+    }
+
+    /// Wrapped row of the text location in its line, and the screen column
+    /// of the caret on that row.
+    fn wrapped_location(&self) -> (usize, usize) {
+        let line = self.line(self.location.y).unwrap_or_default();
+        let rows = self.wrapped_rows(&line);
+        let row = rows
+            .iter()
+            .rposition(|row| row.start <= self.location.x)
+            .unwrap_or_default();
+        let marker_width = if row > 0 { self.show_break_width() } else { 0 };
+        let column =
+            marker_width + line.width_until(self.location.x) - line.width_until(rows[row].start);
+
+        (row, column.min(self.text_width().saturating_sub(1)))
+    }
+
+    /// Moves the text location to the grapheme on the screen column of a
+    /// wrapped row, given as a line index and a row of that line.
+    fn move_to_row(&mut self, (line_index, row): (usize, usize), column: usize) {
+        let line = self.line(line_index).unwrap_or_default();
+        let rows = self.wrapped_rows(&line);
+        let row = std::cmp::min(row, rows.len() - 1);
+        let graphemes = rows[row].clone();
+        let marker_width = if row > 0 { self.show_break_width() } else { 0 };
+        let text_column = line.width_until(graphemes.start) + column.saturating_sub(marker_width);
+        // Only the last row ends where the next grapheme would go.
+        let last_grapheme = if row + 1 < rows.len() {
+            graphemes.end.saturating_sub(1).max(graphemes.start)
+        } else {
+            graphemes.end
+        };
+
+        self.location.y = line_index;
+        self.location.x = line
+            .grapheme_index_at_column(text_column)
+            .clamp(graphemes.start, last_grapheme);
+    }
+
+    fn next_row(&self, (line_index, row): (usize, usize)) -> Option<(usize, usize)> {
+        if row + 1 < self.row_count(line_index) {
+            Some((line_index, row + 1))
+        } else if line_index + 1 < self.buffer.line_count() {
+            Some((line_index + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    fn previous_row(&self, (line_index, row): (usize, usize)) -> Option<(usize, usize)> {
+        if row > 0 {
+            Some((line_index, row - 1))
+        } else if line_index > 0 {
+            Some((line_index - 1, self.row_count(line_index - 1) - 1))
+        } else {
+            None
+        }
+    }
+
+    /// Screen row a wrapped row is drawn on, if it's inside the view.
+    fn screen_row(&self, (line_index, row): (usize, usize)) -> Option<usize> {
+        let height = self.height as usize;
+        let mut screen_row = 0;
+        let mut first_row = self.first_row;
+
+        for current_line in self.scroll_offset.y..line_index {
+            screen_row += self.row_count(current_line).saturating_sub(first_row);
+            if screen_row >= height {
+                return None;
+            }
+            first_row = 0;
+        }
+
+        let screen_row = (screen_row + row).checked_sub(first_row)?;
+        (screen_row < height).then_some(screen_row)
+    }
+
+    /// The last line on screen, and its last row on screen when lines wrap.
+    fn last_visible_row(&self) -> (usize, usize) {
+        let last_line = self.buffer.line_count().saturating_sub(1);
+
+        if !self.settings.wrap {
+            let last_visible_line = self.scroll_offset.y + (self.height as usize).saturating_sub(1);
+            return (std::cmp::min(last_visible_line, last_line), 0);
+        }
+
+        let mut last_row = (self.scroll_offset.y, self.first_row);
+        for _ in 1..self.height {
+            let Some(next_row) = self.next_row(last_row) else {
+                break;
+            };
+            last_row = next_row;
+        }
+        last_row
+    }
+
+    /// Moves the text location inside the visible area after the view was
+    /// scrolled without moving the caret.
+    fn move_location_into_view(&mut self) {
+        let (last_visible_line, _) = self.last_visible_row();
+
+        if self.location.y < self.scroll_offset.y {
+            self.move_to_line(self.scroll_offset.y);
+        } else if self.location.y > last_visible_line {
+            self.move_to_line(last_visible_line);
+        }
+
+        let column = self.location_column();
+        let last_visible_column = self.scroll_offset.x + self.text_width().saturating_sub(1);
+        if let Some(line) = self.line(self.location.y) {
+            if column < self.scroll_offset.x || column > last_visible_column {
+                let visible_column = column.clamp(self.scroll_offset.x, last_visible_column);
+                self.location.x = line.grapheme_index_at_column(visible_column);
+            }
+        }
+
+        self.position.x =
+            self.gutter_width() + self.location_column().saturating_sub(self.scroll_offset.x);
+        self.position.y = self.location.y.saturating_sub(self.scroll_offset.y);
+        self.redraw_relative_line_numbers();
+    }
+
+    /// Relative line numbers count from the caret line, so they change
+    /// whenever the caret moves to another line.
+    fn redraw_relative_line_numbers(&mut self) {
+        if self.settings.relative_number && self.numbered_line != self.location.y {
+            self.needs_redraw = true;
+        }
+    }
 }
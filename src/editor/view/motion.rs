@@ -0,0 +1,409 @@
+use super::{
+    super::terminal::Position,
+    buffer::{Buffer, Line},
+};
+
+/// Where a caret moves in normal and visual mode, and what operators act
+/// on in between.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// Screen rows, which differ from lines when long lines wrap.
+    RowUp,
+    RowDown,
+    WordForward {
+        big: bool,
+    },
+    WordBackward {
+        big: bool,
+    },
+    /// The end of the word. `stay` keeps a caret already at the end of a
+    /// word there, as `cw` does.
+    WordEnd {
+        big: bool,
+        stay: bool,
+    },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// The line given by the count, the first one by default.
+    FirstLine,
+    /// The line given by the count, the last one by default.
+    LastLine,
+    FindChar(CharSearch),
+    /// The last `f`, `t`, `F` or `T`, the other way with `reverse`.
+    RepeatFind {
+        reverse: bool,
+    },
+    ParagraphForward,
+    ParagraphBackward,
+    /// A mark, or only its line unless `exact`.
+    Mark {
+        name: char,
+        exact: bool,
+    },
+    SearchNext {
+        reverse: bool,
+    },
+}
+
+/// How much of the text between the caret and where a motion goes an
+/// operator takes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MotionKind {
+    /// Up to the grapheme the motion goes to, leaving it out.
+    Exclusive,
+    /// Up to the grapheme the motion goes to, taking it.
+    Inclusive,
+    /// Every line from the caret's to the one the motion goes to.
+    Linewise,
+}
+
+/// A search for a character within the line, like `fx` or `Tx`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct CharSearch {
+    pub target: char,
+    pub forward: bool,
+    /// Whether to stop next to the character instead of on it.
+    pub till: bool,
+}
+
+/// What came of a motion: the notice to show after moving, or the error
+/// keeping it from moving. Motions with nowhere to go fail with no error,
+/// which vim only beeps for.
+pub type MotionResult = Result<Option<String>, Option<String>>;
+
+impl Motion {
+    /// The kind of the motion, where repeating a character search takes the
+    /// kind of the direction it goes.
+    pub fn kind(self, last_find: Option<CharSearch>) -> MotionKind {
+        match self {
+            Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine => {
+                MotionKind::Linewise
+            }
+            Motion::Mark { exact: false, .. } => MotionKind::Linewise,
+            Motion::WordEnd { .. } | Motion::LineEnd => MotionKind::Inclusive,
+            Motion::FindChar(search) if search.forward => MotionKind::Inclusive,
+            Motion::RepeatFind { reverse } => match last_find {
+                Some(search) if search.forward != reverse => MotionKind::Inclusive,
+                _ => MotionKind::Exclusive,
+            },
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+/// Classes of graphemes, words being runs of graphemes of one class.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum GraphemeClass {
+    /// Spaces, tabs and line ends.
+    Blank,
+    Punctuation,
+    /// Letters, digits and `_`, or anything but blanks for WORDS.
+    Word,
+}
+
+pub(super) fn class(grapheme: &str, big: bool) -> GraphemeClass {
+    match grapheme.chars().next() {
+        None => GraphemeClass::Blank,
+        Some(c) if c.is_whitespace() => GraphemeClass::Blank,
+        Some(_) if big => GraphemeClass::Word,
+        Some(c) if c.is_alphanumeric() || c == '_' => GraphemeClass::Word,
+        Some(_) => GraphemeClass::Punctuation,
+    }
+}
+
+/// How a walker moved.
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    /// To another grapheme of the line.
+    WithinLine,
+    /// Onto the end of the line.
+    ToLineEnd,
+    /// Onto another line.
+    ToOtherLine,
+    /// Nowhere, being at the start or the end of the buffer.
+    Stuck,
+}
+
+/// A location walking the graphemes of the buffer, where the end of every
+/// line is a blank of its own like in vim.
+struct Walker<'a> {
+    buffer: &'a Buffer,
+    line: Line,
+    at: Position,
+}
+
+impl<'a> Walker<'a> {
+    fn new(buffer: &'a Buffer, at: Position) -> Self {
+        let line = buffer.line(at.y).unwrap_or_default();
+        let at = Position {
+            x: std::cmp::min(at.x, line.grapheme_count()),
+            y: at.y,
+        };
+        Self { buffer, line, at }
+    }
+
+    fn class(&self, big: bool) -> GraphemeClass {
+        class(self.line.grapheme(self.at.x), big)
+    }
+
+    fn on_empty_line(&self) -> bool {
+        self.line.grapheme_count() == 0
+    }
+
+    fn next(&mut self) -> Step {
+        let line_length = self.line.grapheme_count();
+        if self.at.x < line_length {
+            self.at.x += 1;
+            return if self.at.x < line_length {
+                Step::WithinLine
+            } else {
+                Step::ToLineEnd
+            };
+        }
+        if self.at.y + 1 >= self.buffer.line_count() {
+            return Step::Stuck;
+        }
+
+        self.at = Position {
+            x: 0,
+            y: self.at.y + 1,
+        };
+        self.line = self.buffer.line(self.at.y).unwrap_or_default();
+        Step::ToOtherLine
+    }
+
+    fn previous(&mut self) -> Step {
+        if self.at.x > 0 {
+            self.at.x -= 1;
+            return Step::WithinLine;
+        }
+        if self.at.y == 0 {
+            return Step::Stuck;
+        }
+
+        self.at.y -= 1;
+        self.line = self.buffer.line(self.at.y).unwrap_or_default();
+        self.at.x = self.line.grapheme_count();
+        Step::ToOtherLine
+    }
+
+    /// Moves past the graphemes of a class, returning whether it got stuck
+    /// at an end of the buffer.
+    fn skip_class(&mut self, skipped: GraphemeClass, big: bool, forward: bool) -> bool {
+        while self.class(big) == skipped {
+            let step = if forward {
+                self.next()
+            } else {
+                self.previous()
+            };
+            if step == Step::Stuck {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Whether the grapheme at a location is a blank, the end of a line being
+/// one.
+pub fn is_on_blank(buffer: &Buffer, at: Position) -> bool {
+    Walker::new(buffer, at).class(false) == GraphemeClass::Blank
+}
+
+/// Start of the `count`th word after a location, where empty lines count as
+/// words. With `stop_at_line_end`, as when an operator is pending, the last
+/// word stops at the end of its line instead of going on to the next one.
+pub fn word_forward(
+    buffer: &Buffer,
+    from: Position,
+    count: usize,
+    big: bool,
+    stop_at_line_end: bool,
+) -> Position {
+    let mut walker = Walker::new(buffer, from);
+
+    for remaining in (0..count).rev() {
+        let stops_at_line_end = stop_at_line_end && remaining == 0;
+        let start_class = walker.class(big);
+        let on_last_line = walker.at.y + 1 >= buffer.line_count();
+
+        let step = walker.next();
+        if step == Step::Stuck || (step != Step::WithinLine && (on_last_line || stops_at_line_end))
+        {
+            break;
+        }
+
+        let mut reached_end = false;
+        while start_class != GraphemeClass::Blank && walker.class(big) == start_class {
+            let step = walker.next();
+            if step == Step::Stuck || (step != Step::WithinLine && stops_at_line_end) {
+                reached_end = true;
+                break;
+            }
+        }
+        while !reached_end && walker.class(big) == GraphemeClass::Blank {
+            if walker.at.x == 0 && walker.on_empty_line() {
+                break;
+            }
+            let step = walker.next();
+            if step == Step::Stuck || (step != Step::WithinLine && stops_at_line_end) {
+                reached_end = true;
+            }
+        }
+        if reached_end {
+            break;
+        }
+    }
+
+    walker.at
+}
+
+/// Start of the `count`th word before a location, where empty lines count
+/// as words.
+pub fn word_backward(buffer: &Buffer, from: Position, count: usize, big: bool) -> Position {
+    let mut walker = Walker::new(buffer, from);
+
+    for _ in 0..count {
+        if walker.previous() == Step::Stuck {
+            break;
+        }
+
+        let mut on_empty_line = false;
+        while walker.class(big) == GraphemeClass::Blank {
+            if walker.at.x == 0 && walker.on_empty_line() {
+                on_empty_line = true;
+                break;
+            }
+            if walker.previous() == Step::Stuck {
+                return walker.at;
+            }
+        }
+        if on_empty_line {
+            continue;
+        }
+
+        if walker.skip_class(walker.class(big), big, false) {
+            return walker.at;
+        }
+        walker.next();
+    }
+
+    walker.at
+}
+
+/// End of the `count`th word after a location. With `stay`, a location
+/// already at the end of a word counts as the first one.
+pub fn word_end(buffer: &Buffer, from: Position, count: usize, big: bool, stay: bool) -> Position {
+    let mut walker = Walker::new(buffer, from);
+    let mut stay = stay;
+
+    for _ in 0..count {
+        let start_class = walker.class(big);
+        if walker.next() == Step::Stuck {
+            break;
+        }
+
+        if start_class != GraphemeClass::Blank && walker.class(big) == start_class {
+            if walker.skip_class(start_class, big, true) {
+                break;
+            }
+        } else if !stay || start_class == GraphemeClass::Blank {
+            if walker.skip_class(GraphemeClass::Blank, big, true) {
+                break;
+            }
+            if walker.skip_class(walker.class(big), big, true) {
+                break;
+            }
+        }
+        walker.previous();
+        stay = false;
+    }
+
+    walker.at
+}
+
+/// The `count`th empty line after or before a line, past the paragraph the
+/// line is in. Going forward without one ends on the last grapheme of the
+/// buffer, and going backward on its start.
+pub fn paragraph(buffer: &Buffer, from_line: usize, count: usize, forward: bool) -> Position {
+    let last_line = buffer.line_count().saturating_sub(1);
+    let is_empty = |line_index: usize| buffer.line_length(line_index) == 0;
+    let mut line_index = from_line;
+
+    for _ in 0..count {
+        let mut left_empty_lines = false;
+        let mut first = true;
+        loop {
+            if !is_empty(line_index) {
+                left_empty_lines = true;
+            }
+            if !first && left_empty_lines && is_empty(line_index) {
+                break;
+            }
+            first = false;
+
+            if forward && line_index < last_line {
+                line_index += 1;
+            } else if !forward && line_index > 0 {
+                line_index -= 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    if forward && line_index == last_line && !is_empty(line_index) {
+        Position {
+            x: buffer.line_length(line_index) - 1,
+            y: line_index,
+        }
+    } else {
+        Position {
+            x: 0,
+            y: line_index,
+        }
+    }
+}
+
+/// Grapheme index the `count`th search for a character in a line stops at.
+/// Repeating a `t` search right next to its character skips that one, so
+/// the caret moves.
+pub fn find_char(
+    line: &Line,
+    from: usize,
+    search: CharSearch,
+    count: usize,
+    repeating: bool,
+) -> Option<usize> {
+    let target = search.target.to_string();
+    let mut x = from;
+    let mut can_stop = !(repeating && search.till && count == 1);
+
+    for _ in 0..count {
+        loop {
+            if search.forward {
+                x += 1;
+                if x >= line.grapheme_count() {
+                    return None;
+                }
+            } else {
+                x = x.checked_sub(1)?;
+            }
+            if line.grapheme(x) == target && can_stop {
+                break;
+            }
+            can_stop = true;
+        }
+    }
+
+    match (search.till, search.forward) {
+        (false, _) => Some(x),
+        (true, true) => Some(x - 1),
+        (true, false) => Some(x + 1),
+    }
+}
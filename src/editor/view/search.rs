@@ -0,0 +1,88 @@
+use regex::{Regex, RegexBuilder};
+
+use super::super::terminal::Position;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+impl SearchDirection {
+    pub fn reversed(self) -> Self {
+        match self {
+            SearchDirection::Forward => SearchDirection::Backward,
+            SearchDirection::Backward => SearchDirection::Forward,
+        }
+    }
+
+    /// Character typed to start a search in this direction.
+    pub fn prompt(self) -> char {
+        match self {
+            SearchDirection::Forward => '/',
+            SearchDirection::Backward => '?',
+        }
+    }
+
+    fn wrap_notice(self) -> &'static str {
+        match self {
+            SearchDirection::Forward => "search hit BOTTOM, continuing at TOP",
+            SearchDirection::Backward => "search hit TOP, continuing at BOTTOM",
+        }
+    }
+}
+
+/// A searched regular expression, kept to repeat the search with `n` and
+/// `N` and to highlight its matches.
+#[derive(Clone)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub direction: SearchDirection,
+    pub regex: Regex,
+}
+
+impl SearchQuery {
+    pub fn new(pattern: &str, direction: SearchDirection) -> Result<Self, String> {
+        Ok(Self {
+            pattern: pattern.to_string(),
+            direction,
+            regex: build_regex(pattern, is_smart_case_insensitive(pattern))?,
+        })
+    }
+}
+
+pub fn build_regex(pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| format!("Invalid pattern: {pattern}: {err}"))
+}
+
+/// Smart case: a pattern ignores case unless it has an uppercase letter
+/// that isn't part of an escape like `\W`.
+pub fn is_smart_case_insensitive(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c.is_uppercase() {
+            return false;
+        }
+    }
+
+    true
+}
+
+pub struct SearchResult {
+    pub location: Position,
+    pub wrapped: bool,
+}
+
+impl SearchResult {
+    /// Notice shown when the match was found after wrapping around the end
+    /// of the buffer.
+    pub fn notice(&self, direction: SearchDirection) -> Option<String> {
+        self.wrapped.then(|| direction.wrap_notice().to_string())
+    }
+}
@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+use super::super::terminal::Position;
+
+/// Shape of a visual mode selection.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectionKind {
+    /// Every character from one end to the other.
+    Characterwise,
+    /// Whole lines.
+    Linewise,
+    /// A rectangle of screen columns.
+    Blockwise,
+}
+
+/// Text selected in visual mode, or for an operator to act on, reaching
+/// from the anchor to the caret.
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub kind: SelectionKind,
+    pub anchor: Position,
+    /// Whether it was made in visual mode, so its ends are remembered in
+    /// the `'<` and `'>` marks.
+    pub visual: bool,
+}
+
+/// The part of a line inside a selection.
+pub struct SelectedSpan {
+    pub line_index: usize,
+    pub graphemes: Range<usize>,
+    /// Whether the line break ending the line is selected too.
+    pub line_break: bool,
+}
+
+/// Text taken from a selection, remembering its shape.
+#[derive(Clone)]
+pub struct SelectedText {
+    /// The selected text with `\n` between lines, ending with one when the
+    /// last line break is selected.
+    pub text: String,
+    pub kind: SelectionKind,
+}
+
+impl SelectedText {
+    pub fn line_count(&self) -> usize {
+        self.text.trim_end_matches('\n').matches('\n').count() + 1
+    }
+}
+
+/// How the operators changing case change it.
+#[derive(Clone, Copy)]
+pub enum CaseChange {
+    Toggle,
+    Lower,
+    Upper,
+}
+
+impl CaseChange {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            CaseChange::Toggle => text
+                .chars()
+                .map(|c| {
+                    if c.is_lowercase() {
+                        c.to_uppercase().to_string()
+                    } else {
+                        c.to_lowercase().to_string()
+                    }
+                })
+                .collect(),
+            CaseChange::Lower => text.to_lowercase(),
+            CaseChange::Upper => text.to_uppercase(),
+        }
+    }
+}
+
+/// Orders two locations by line and then by grapheme.
+pub fn ordered(first: Position, second: Position) -> (Position, Position) {
+    if (first.y, first.x) <= (second.y, second.x) {
+        (first, second)
+    } else {
+        (second, first)
+    }
+}
@@ -0,0 +1,249 @@
+/// Names and short names of the options `:set` understands.
+const OPTIONS: &[(&str, &str)] = &[
+    ("undofile", "udf"),
+    ("wrap", "wrap"),
+    ("showbreak", "sbr"),
+    ("number", "nu"),
+    ("relativenumber", "rnu"),
+    ("tabstop", "ts"),
+    ("shiftwidth", "sw"),
+    ("expandtab", "et"),
+    ("colorcolumn", "cc"),
+];
+
+/// The widest `tabstop`, as in vim, so a tab stays a sane number of
+/// spaces.
+pub const MAX_TAB_STOP: usize = 9999;
+
+#[derive(Clone, PartialEq)]
+enum OptionValue {
+    Bool(bool),
+    Text(String),
+    Number(usize),
+}
+
+enum SetAction {
+    Enable,
+    Disable,
+    Toggle,
+    Show,
+}
+
+/// Options every file can set for itself, from `.editorconfig` or the
+/// indentation found in it.
+#[derive(Clone, Copy)]
+struct FileOptions {
+    tab_stop: usize,
+    shift_width: usize,
+    expand_tab: bool,
+    color_column: usize,
+}
+
+/// Options changed at runtime with `:set`.
+pub struct Settings {
+    pub undo_file: bool,
+    /// Whether long lines continue on the next screen rows instead of
+    /// scrolling horizontally.
+    pub wrap: bool,
+    /// Marker drawn at the start of the rows a wrapped line continues on.
+    pub show_break: String,
+    /// Whether the gutter numbers lines, counting from the caret line
+    /// with `relative_number` too.
+    pub number: bool,
+    pub relative_number: bool,
+    /// Columns between tab stops.
+    pub tab_stop: usize,
+    /// Columns `>>` and `<<` shift lines by, or `tab_stop` when it's 0.
+    pub shift_width: usize,
+    /// Whether indenting inserts spaces instead of tabs.
+    pub expand_tab: bool,
+    /// Column highlighted to show where lines get too long, counting from
+    /// 1, or 0 to highlight none.
+    pub color_column: usize,
+    /// The file options as given with `:set`, which every file opened
+    /// starts from.
+    file_defaults: FileOptions,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let file_defaults = FileOptions {
+            tab_stop: 8,
+            shift_width: 8,
+            expand_tab: false,
+            color_column: 0,
+        };
+        Self {
+            undo_file: true,
+            wrap: false,
+            show_break: "↪ ".to_string(),
+            number: false,
+            relative_number: false,
+            tab_stop: file_defaults.tab_stop,
+            shift_width: file_defaults.shift_width,
+            expand_tab: file_defaults.expand_tab,
+            color_column: file_defaults.color_column,
+            file_defaults,
+        }
+    }
+}
+
+impl Settings {
+    /// Goes back to the file options given with `:set`, dropping the ones
+    /// the last file set for itself.
+    pub fn reset_file_options(&mut self) {
+        self.tab_stop = self.file_defaults.tab_stop;
+        self.shift_width = self.file_defaults.shift_width;
+        self.expand_tab = self.file_defaults.expand_tab;
+        self.color_column = self.file_defaults.color_column;
+    }
+
+    /// Applies one `:set` argument, like `name`, `noname`, `invname`,
+    /// `name!`, `name?` or `name=value`, returning the text to show when the
+    /// argument asks for the value of an option.
+    pub fn apply(&mut self, argument: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = argument.split_once(['=', ':']) {
+            let name = Self::full_name(name)?;
+            return match self.value(name) {
+                OptionValue::Text(_) => {
+                    self.set_value(name, OptionValue::Text(value.to_string()));
+                    Ok(None)
+                }
+                OptionValue::Number(_) => {
+                    let value = value
+                        .parse()
+                        .map_err(|_| format!("Number required after =: {argument}"))?;
+                    if value == 0 && name == "tabstop" {
+                        return Err(format!("Argument must be positive: {argument}"));
+                    }
+                    if value > MAX_TAB_STOP && name == "tabstop" {
+                        return Err(format!("Invalid argument: {argument}"));
+                    }
+                    self.set_value(name, OptionValue::Number(value));
+                    Ok(None)
+                }
+                OptionValue::Bool(_) => Err(format!("Invalid argument: {argument}")),
+            };
+        }
+
+        let (name, action) = if let Some(name) = argument.strip_suffix('?') {
+            (name, SetAction::Show)
+        } else if let Some(name) = argument.strip_suffix('!') {
+            (name, SetAction::Toggle)
+        } else if let Some(name) = Self::strip_known_prefix(argument, "inv") {
+            (name, SetAction::Toggle)
+        } else if let Some(name) = Self::strip_known_prefix(argument, "no") {
+            (name, SetAction::Disable)
+        } else {
+            (argument, SetAction::Enable)
+        };
+        let name = Self::full_name(name)?;
+
+        match (self.value(name), action) {
+            (_, SetAction::Show) => return Ok(Some(self.describe(name))),
+            (OptionValue::Bool(current), SetAction::Toggle) => {
+                self.set_value(name, OptionValue::Bool(!current));
+            }
+            (OptionValue::Bool(_), SetAction::Disable) => {
+                self.set_value(name, OptionValue::Bool(false));
+            }
+            (OptionValue::Bool(_), SetAction::Enable) => {
+                self.set_value(name, OptionValue::Bool(true));
+            }
+            (OptionValue::Text(_) | OptionValue::Number(_), SetAction::Enable) => {
+                return Ok(Some(self.describe(name)));
+            }
+            (
+                OptionValue::Text(_) | OptionValue::Number(_),
+                SetAction::Toggle | SetAction::Disable,
+            ) => {
+                return Err(format!("Invalid argument: {argument}"));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Describes every option, used by `:set` without arguments.
+    pub fn describe_all(&self) -> String {
+        OPTIONS
+            .iter()
+            .map(|(name, _)| self.describe(name))
+            .collect::<Vec<String>>()
+            .join("  ")
+    }
+
+    fn describe(&self, name: &str) -> String {
+        match self.value(name) {
+            OptionValue::Bool(true) => name.to_string(),
+            OptionValue::Bool(false) => format!("no{name}"),
+            OptionValue::Text(value) => format!("{name}={value}"),
+            OptionValue::Number(value) => format!("{name}={value}"),
+        }
+    }
+
+    /// Columns one level of indentation takes.
+    pub fn effective_shift_width(&self) -> usize {
+        if self.shift_width == 0 {
+            self.tab_stop
+        } else {
+            self.shift_width
+        }
+    }
+
+    fn strip_known_prefix<'a>(argument: &'a str, prefix: &str) -> Option<&'a str> {
+        argument
+            .strip_prefix(prefix)
+            .filter(|name| Self::full_name(name).is_ok())
+    }
+
+    fn full_name(name: &str) -> Result<&'static str, String> {
+        OPTIONS
+            .iter()
+            .find(|(full_name, short_name)| *full_name == name || *short_name == name)
+            .map(|(full_name, _)| *full_name)
+            .ok_or_else(|| format!("Unknown option: {name}"))
+    }
+
+    fn value(&self, name: &str) -> OptionValue {
+        match name {
+            "undofile" => OptionValue::Bool(self.undo_file),
+            "wrap" => OptionValue::Bool(self.wrap),
+            "showbreak" => OptionValue::Text(self.show_break.clone()),
+            "number" => OptionValue::Bool(self.number),
+            "relativenumber" => OptionValue::Bool(self.relative_number),
+            "tabstop" => OptionValue::Number(self.tab_stop),
+            "shiftwidth" => OptionValue::Number(self.shift_width),
+            "expandtab" => OptionValue::Bool(self.expand_tab),
+            "colorcolumn" => OptionValue::Number(self.color_column),
+            _ => unreachable!("option {name} isn't listed in OPTIONS"),
+        }
+    }
+
+    fn set_value(&mut self, name: &str, value: OptionValue) {
+        match (name, value) {
+            ("undofile", OptionValue::Bool(value)) => self.undo_file = value,
+            ("wrap", OptionValue::Bool(value)) => self.wrap = value,
+            ("showbreak", OptionValue::Text(value)) => self.show_break = value,
+            ("number", OptionValue::Bool(value)) => self.number = value,
+            ("relativenumber", OptionValue::Bool(value)) => self.relative_number = value,
+            ("tabstop", OptionValue::Number(value)) => {
+                self.tab_stop = value;
+                self.file_defaults.tab_stop = value;
+            }
+            ("shiftwidth", OptionValue::Number(value)) => {
+                self.shift_width = value;
+                self.file_defaults.shift_width = value;
+            }
+            ("expandtab", OptionValue::Bool(value)) => {
+                self.expand_tab = value;
+                self.file_defaults.expand_tab = value;
+            }
+            ("colorcolumn", OptionValue::Number(value)) => {
+                self.color_column = value;
+                self.file_defaults.color_column = value;
+            }
+            _ => unreachable!("option {name} isn't listed in OPTIONS"),
+        }
+    }
+}
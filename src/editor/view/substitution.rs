@@ -0,0 +1,184 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+use super::buffer::Buffer;
+
+/// A match of a running substitution, as a byte range of its line.
+pub struct SubstitutionMatch {
+    pub line_index: usize,
+    pub bytes: Range<usize>,
+}
+
+/// A running `:substitute`, walking the matches of its line range from top
+/// to bottom so each one can be replaced or skipped.
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+    typed_replacement: String,
+    global: bool,
+    line_index: usize,
+    last_line_index: usize,
+    search_from: usize,
+    current: Option<SubstitutionMatch>,
+    substitution_count: usize,
+    changed_line_count: usize,
+    last_changed_line: Option<usize>,
+}
+
+impl Substitution {
+    /// Creates a substitution over `lines`, translating the vim style
+    /// `replacement` where `&` and `\0`-`\9` insert the match and its groups
+    /// and `\r` or `\n` break the line.
+    pub fn new(regex: Regex, replacement: &str, global: bool, lines: Range<usize>) -> Self {
+        Self {
+            regex,
+            replacement: Self::translate_replacement(replacement),
+            typed_replacement: replacement.to_string(),
+            global,
+            line_index: lines.start,
+            last_line_index: lines.end.saturating_sub(1),
+            search_from: 0,
+            current: None,
+            substitution_count: 0,
+            changed_line_count: 0,
+            last_changed_line: None,
+        }
+    }
+
+    fn translate_replacement(replacement: &str) -> String {
+        let mut translated = String::new();
+        let mut chars = replacement.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '&' => translated.push_str("${0}"),
+                '$' => translated.push_str("$$"),
+                '\\' => match chars.next() {
+                    Some(digit @ '0'..='9') => translated.push_str(&format!("${{{digit}}}")),
+                    Some('r' | 'n') => translated.push('\n'),
+                    Some('t') => translated.push('\t'),
+                    Some('$') => translated.push_str("$$"),
+                    Some(escaped) => translated.push(escaped),
+                    None => translated.push('\\'),
+                },
+                _ => translated.push(c),
+            }
+        }
+
+        translated
+    }
+
+    /// Question asked for every match in confirm mode.
+    pub fn prompt(&self) -> String {
+        format!("replace with {} (y/n/a/q/l)?", self.typed_replacement)
+    }
+
+    pub fn current_match(&self) -> Option<&SubstitutionMatch> {
+        self.current.as_ref()
+    }
+
+    pub fn substitution_count(&self) -> usize {
+        self.substitution_count
+    }
+
+    pub fn changed_line_count(&self) -> usize {
+        self.changed_line_count
+    }
+
+    pub fn last_changed_line(&self) -> Option<usize> {
+        self.last_changed_line
+    }
+
+    /// Moves to the next match inside the range, returning whether there
+    /// is one.
+    pub fn find_next(&mut self, buffer: &Buffer) -> bool {
+        self.current = None;
+
+        while self.line_index <= self.last_line_index {
+            let Some(line) = buffer.line_text(self.line_index) else {
+                break;
+            };
+
+            while self.search_from < line.len() && !line.is_char_boundary(self.search_from) {
+                self.search_from += 1;
+            }
+
+            if self.search_from <= line.len() {
+                if let Some(matched) = self.regex.find_at(&line, self.search_from) {
+                    self.current = Some(SubstitutionMatch {
+                        line_index: self.line_index,
+                        bytes: matched.range(),
+                    });
+                    return true;
+                }
+            }
+
+            self.move_to_next_line();
+        }
+
+        false
+    }
+
+    /// Replaces the current match and moves past it.
+    pub fn replace(&mut self, buffer: &mut Buffer) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+        let Some(line) = buffer.line_text(current.line_index) else {
+            return;
+        };
+        let Some(captures) = self.regex.captures_at(&line, current.bytes.start) else {
+            return;
+        };
+
+        let mut replacement = String::new();
+        captures.expand(&self.replacement, &mut replacement);
+
+        buffer.replace(current.line_index, current.bytes.clone(), &replacement);
+        let inserted_lines = replacement.matches('\n').count();
+        self.substitution_count += 1;
+        if self.last_changed_line != Some(current.line_index) {
+            self.changed_line_count += 1;
+        }
+        // The rest of the line moves down when the replacement breaks it.
+        self.last_changed_line = Some(current.line_index + inserted_lines);
+
+        let last_line_length = replacement.rsplit('\n').next().unwrap_or_default().len();
+        self.last_line_index += inserted_lines;
+        self.line_index = current.line_index + inserted_lines;
+        self.search_from = if inserted_lines > 0 {
+            last_line_length
+        } else {
+            current.bytes.start + last_line_length
+        };
+        self.move_past_match(current.bytes.is_empty());
+    }
+
+    /// Leaves the current match as it is and moves past it.
+    pub fn skip(&mut self) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+
+        self.line_index = current.line_index;
+        self.search_from = current.bytes.end;
+        self.move_past_match(current.bytes.is_empty());
+    }
+
+    /// Continues on the next line unless the substitution is global, and
+    /// steps over the next char after an empty match so it isn't matched
+    /// again.
+    fn move_past_match(&mut self, was_empty: bool) {
+        if !self.global {
+            self.move_to_next_line();
+        } else if was_empty {
+            self.search_from += 1;
+        }
+    }
+
+    fn move_to_next_line(&mut self) {
+        self.line_index += 1;
+        self.search_from = 0;
+    }
+}
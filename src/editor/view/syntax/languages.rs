@@ -0,0 +1,290 @@
+use regex::Regex;
+
+use super::{SyntaxDefinition, SyntaxRule, TokenKind};
+
+const DECIMAL_NUMBER: &str = r"\b\d[\d_]*(?:\.\d[\d_]*)?(?:[eE][+-]?\d+)?\b";
+const HEX_NUMBER: &str = r"\b0[xX][0-9a-fA-F_]+\b";
+const FUNCTION_CALL: &str = r"\b([a-zA-Z_]\w*)\s*\(";
+const UPPERCASE_CONSTANT: &str = r"\b[A-Z][A-Z0-9_]+\b";
+const CAPITALIZED_TYPE: &str = r"\b[A-Z]\w*\b";
+
+/// The built-in definition for a file type, as named by `Buffer::file_type`.
+pub fn definition(file_type: &str) -> Option<SyntaxDefinition> {
+    let rules = match file_type {
+        "rust" => rust(),
+        "json" => json(),
+        "yaml" => yaml(),
+        "toml" => toml(),
+        "markdown" => markdown(),
+        "c" => c(),
+        "python" => python(),
+        _ => return None,
+    };
+
+    Some(SyntaxDefinition {
+        name: file_type.to_string(),
+        extensions: Vec::new(),
+        shebangs: Vec::new(),
+        rules,
+    })
+}
+
+/// The built-in file type of scripts run by `interpreter`.
+pub fn interpreter_file_type(interpreter: &str) -> Option<&'static str> {
+    match interpreter {
+        "python" | "python2" | "python3" => Some("python"),
+        _ => None,
+    }
+}
+
+fn rust() -> Vec<SyntaxRule> {
+    vec![
+        region(TokenKind::Comment, r"/\*", r"\*/", None, true),
+        rule(TokenKind::Comment, r"//.*"),
+        region(TokenKind::String, r#"\bb?r#""#, r##""#"##, None, true),
+        region(TokenKind::String, r#"\bb?r""#, r#"""#, None, true),
+        region(TokenKind::String, r#"\bb""#, r#"""#, Some('\\'), true),
+        region(TokenKind::String, r#"""#, r#"""#, Some('\\'), true),
+        rule(TokenKind::String, r"b?'(?:\\.|\\u\{[0-9a-fA-F]+\}|[^\\'])'"),
+        rule(TokenKind::Macro, r"#!?\[[^\]]*\]"),
+        rule(TokenKind::Macro, r"\b[a-zA-Z_]\w*!"),
+        words(
+            TokenKind::Keyword,
+            &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+                "move", "mut", "pub", "ref", "return", "self", "static", "struct", "super",
+                "trait", "type", "unsafe", "use", "where", "while",
+            ],
+        ),
+        words(
+            TokenKind::Constant,
+            &["true", "false", "None", "Some", "Ok", "Err"],
+        ),
+        words(
+            TokenKind::Type,
+            &[
+                "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128",
+                "usize", "f32", "f64", "bool", "char", "str", "Self",
+            ],
+        ),
+        rule(TokenKind::Constant, UPPERCASE_CONSTANT),
+        rule(TokenKind::Type, CAPITALIZED_TYPE),
+        rule(TokenKind::Function, FUNCTION_CALL),
+        rule(TokenKind::Number, HEX_NUMBER),
+        rule(TokenKind::Number, DECIMAL_NUMBER),
+    ]
+}
+
+fn json() -> Vec<SyntaxRule> {
+    vec![
+        rule(TokenKind::Key, r#"("(?:\\.|[^"\\])*")\s*:"#),
+        region(TokenKind::String, r#"""#, r#"""#, Some('\\'), false),
+        words(TokenKind::Constant, &["true", "false", "null"]),
+        rule(TokenKind::Number, r"-?\b\d+(?:\.\d+)?(?:[eE][+-]?\d+)?\b"),
+    ]
+}
+
+fn yaml() -> Vec<SyntaxRule> {
+    vec![
+        rule(TokenKind::Comment, r"(?:^|\s)(#.*)"),
+        rule(TokenKind::Keyword, r"^(?:---|\.\.\.)"),
+        rule(
+            TokenKind::Key,
+            r#"^\s*(?:-\s+)?([^\s#'"\-][^:#]*?|"[^"]*"|'[^']*')\s*:(?:\s|$)"#,
+        ),
+        region(TokenKind::String, r#"""#, r#"""#, Some('\\'), false),
+        region(TokenKind::String, "'", "'", None, false),
+        rule(TokenKind::Macro, r"[&*][\w-]+"),
+        words(
+            TokenKind::Constant,
+            &["true", "false", "yes", "no", "on", "off", "null"],
+        ),
+        rule(TokenKind::Constant, r"(?:^|\s)(~)(?:\s|$)"),
+        rule(TokenKind::Number, r"-?\b\d+(?:\.\d+)?(?:[eE][+-]?\d+)?\b"),
+    ]
+}
+
+fn toml() -> Vec<SyntaxRule> {
+    vec![
+        rule(TokenKind::Comment, r"#.*"),
+        rule(TokenKind::Heading, r"^\s*(\[\[?[^\]]*\]\]?)"),
+        rule(TokenKind::Key, r#"^\s*([\w.\-]+|"[^"]*"|'[^']*')\s*="#),
+        region(TokenKind::String, r#"""""#, r#"""""#, Some('\\'), true),
+        region(TokenKind::String, "'''", "'''", None, true),
+        region(TokenKind::String, r#"""#, r#"""#, Some('\\'), false),
+        region(TokenKind::String, "'", "'", None, false),
+        words(TokenKind::Constant, &["true", "false", "inf", "nan"]),
+        rule(
+            TokenKind::Number,
+            r"\b\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:\d{2})?)?",
+        ),
+        rule(TokenKind::Number, HEX_NUMBER),
+        rule(
+            TokenKind::Number,
+            r"[+-]?\b\d[\d_]*(?:\.\d[\d_]*)?(?:[eE][+-]?\d+)?\b",
+        ),
+    ]
+}
+
+fn markdown() -> Vec<SyntaxRule> {
+    vec![
+        region(
+            TokenKind::Code,
+            r"^\s*(?:```|~~~)",
+            r"^\s*(?:```|~~~)",
+            None,
+            true,
+        ),
+        rule(TokenKind::Heading, r"^#{1,6}(?:\s.*)?$"),
+        rule(TokenKind::Comment, r"^\s*>.*"),
+        rule(TokenKind::Keyword, r"^\s*([-*+]|\d+[.)])\s"),
+        region(TokenKind::Code, "`", "`", None, false),
+        rule(TokenKind::Link, r"!?\[[^\]]*\]\([^)]*\)"),
+        rule(TokenKind::Link, r"<https?://[^>]*>"),
+        rule(TokenKind::Emphasis, r"\*\*[^*]+\*\*|__[^_]+__"),
+        rule(TokenKind::Emphasis, r"\*[^*\s][^*]*\*|\b_[^_\s][^_]*_\b"),
+    ]
+}
+
+fn c() -> Vec<SyntaxRule> {
+    vec![
+        region(TokenKind::Comment, r"/\*", r"\*/", None, true),
+        rule(TokenKind::Comment, r"//.*"),
+        rule(TokenKind::Macro, r"^\s*#\s*\w+"),
+        rule(TokenKind::String, r"<[\w./]+\.h>"),
+        region(TokenKind::String, r#"""#, r#"""#, Some('\\'), false),
+        rule(TokenKind::String, r"'(?:\\.|[^\\'])+'"),
+        words(
+            TokenKind::Keyword,
+            &[
+                "auto", "break", "case", "const", "continue", "default", "do", "else", "enum",
+                "extern", "for", "goto", "if", "inline", "register", "restrict", "return",
+                "sizeof", "static", "struct", "switch", "typedef", "union", "volatile", "while",
+            ],
+        ),
+        words(
+            TokenKind::Type,
+            &[
+                "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned",
+                "bool", "_Bool", "size_t", "FILE",
+            ],
+        ),
+        rule(TokenKind::Type, r"\b\w+_t\b"),
+        words(TokenKind::Constant, &["NULL", "true", "false"]),
+        rule(TokenKind::Constant, UPPERCASE_CONSTANT),
+        rule(TokenKind::Function, FUNCTION_CALL),
+        rule(TokenKind::Number, r"\b0[xX][0-9a-fA-F]+[uUlL]*\b"),
+        rule(
+            TokenKind::Number,
+            r"\b\d+(?:\.\d+)?(?:[eE][+-]?\d+)?[uUlLfF]*\b",
+        ),
+    ]
+}
+
+fn python() -> Vec<SyntaxRule> {
+    vec![
+        rule(TokenKind::Comment, r"#.*"),
+        region(
+            TokenKind::String,
+            r#"\b[rRbBfFuU]{0,2}""""#,
+            r#"""""#,
+            Some('\\'),
+            true,
+        ),
+        region(
+            TokenKind::String,
+            r"\b[rRbBfFuU]{0,2}'''",
+            "'''",
+            Some('\\'),
+            true,
+        ),
+        region(TokenKind::String, r#"""""#, r#"""""#, Some('\\'), true),
+        region(TokenKind::String, "'''", "'''", Some('\\'), true),
+        region(
+            TokenKind::String,
+            r#"\b[rRbBfFuU]{0,2}""#,
+            r#"""#,
+            Some('\\'),
+            false,
+        ),
+        region(
+            TokenKind::String,
+            r"\b[rRbBfFuU]{0,2}'",
+            "'",
+            Some('\\'),
+            false,
+        ),
+        region(TokenKind::String, r#"""#, r#"""#, Some('\\'), false),
+        region(TokenKind::String, "'", "'", Some('\\'), false),
+        rule(TokenKind::Macro, r"^\s*@[\w.]+"),
+        words(
+            TokenKind::Keyword,
+            &[
+                "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+                "del", "elif", "else", "except", "finally", "for", "from", "global", "if",
+                "import", "in", "is", "lambda", "match", "case", "nonlocal", "not", "or", "pass",
+                "raise", "return", "try", "while", "with", "yield", "self",
+            ],
+        ),
+        words(TokenKind::Constant, &["True", "False", "None"]),
+        words(
+            TokenKind::Type,
+            &[
+                "int",
+                "float",
+                "complex",
+                "str",
+                "bytes",
+                "bool",
+                "list",
+                "tuple",
+                "dict",
+                "set",
+                "frozenset",
+                "object",
+            ],
+        ),
+        rule(TokenKind::Constant, UPPERCASE_CONSTANT),
+        rule(TokenKind::Type, CAPITALIZED_TYPE),
+        rule(TokenKind::Function, FUNCTION_CALL),
+        rule(TokenKind::Number, HEX_NUMBER),
+        rule(TokenKind::Number, DECIMAL_NUMBER),
+    ]
+}
+
+fn rule(kind: TokenKind, pattern: &str) -> SyntaxRule {
+    SyntaxRule {
+        kind,
+        start: build(pattern),
+        end: None,
+        escape: None,
+        multiline: false,
+        rules: Vec::new(),
+    }
+}
+
+fn region(
+    kind: TokenKind,
+    start: &str,
+    end: &str,
+    escape: Option<char>,
+    multiline: bool,
+) -> SyntaxRule {
+    SyntaxRule {
+        kind,
+        start: build(start),
+        end: Some(build(end)),
+        escape,
+        multiline,
+        rules: Vec::new(),
+    }
+}
+
+fn words(kind: TokenKind, words: &[&str]) -> SyntaxRule {
+    rule(kind, &format!(r"\b(?:{})\b", words.join("|")))
+}
+
+fn build(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("Built-in syntax patterns are valid")
+}
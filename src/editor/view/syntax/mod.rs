@@ -0,0 +1,411 @@
+use std::{ops::Range, path::Path, rc::Rc};
+
+use crossterm::style::Color;
+use regex::Regex;
+
+use super::buffer::Buffer;
+
+mod languages;
+mod user;
+
+/// What a piece of highlighted text is, deciding the color it's drawn in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Comment,
+    String,
+    Number,
+    Constant,
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Key,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+}
+
+impl TokenKind {
+    pub fn color(self) -> Color {
+        match self {
+            TokenKind::Comment => Color::DarkGrey,
+            TokenKind::String | TokenKind::Code => Color::Green,
+            TokenKind::Number | TokenKind::Constant => Color::Cyan,
+            TokenKind::Keyword => Color::Magenta,
+            TokenKind::Type => Color::Yellow,
+            TokenKind::Function | TokenKind::Key | TokenKind::Heading => Color::Blue,
+            TokenKind::Macro => Color::DarkCyan,
+            TokenKind::Emphasis => Color::DarkYellow,
+            TokenKind::Link => Color::DarkBlue,
+        }
+    }
+
+    /// Kind named in a syntax file, like `keyword`.
+    fn from_name(name: &str) -> Option<Self> {
+        let kind = match name {
+            "comment" => TokenKind::Comment,
+            "string" => TokenKind::String,
+            "number" => TokenKind::Number,
+            "constant" => TokenKind::Constant,
+            "keyword" => TokenKind::Keyword,
+            "type" => TokenKind::Type,
+            "function" => TokenKind::Function,
+            "macro" => TokenKind::Macro,
+            "key" => TokenKind::Key,
+            "heading" => TokenKind::Heading,
+            "emphasis" => TokenKind::Emphasis,
+            "code" => TokenKind::Code,
+            "link" => TokenKind::Link,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+}
+
+/// A pattern of a language. Rules without `end` color what `start` matches,
+/// or only its first capture group when it has one. Rules with `end` color
+/// a region from `start` to the next `end` not preceded by `escape`, which
+/// may span lines when `multiline` is set, and highlight the inside of the
+/// region with their own `rules`.
+pub struct SyntaxRule {
+    pub kind: TokenKind,
+    pub start: Regex,
+    pub end: Option<Regex>,
+    pub escape: Option<char>,
+    pub multiline: bool,
+    pub rules: Vec<SyntaxRule>,
+}
+
+/// The rules of a language and the files it's used for. When several rules
+/// match, the one starting first wins, and the one listed first among those
+/// starting together.
+pub struct SyntaxDefinition {
+    pub name: String,
+    pub extensions: Vec<String>,
+    /// Interpreters a `#!` line runs, like `python3`.
+    pub shebangs: Vec<String>,
+    pub rules: Vec<SyntaxRule>,
+}
+
+impl SyntaxDefinition {
+    /// Rule of a region nested in others, given as the rule indices leading
+    /// to it from the outermost region.
+    fn rule_at(&self, path: &[usize]) -> Option<&SyntaxRule> {
+        let (first, rest) = path.split_first()?;
+        rest.iter()
+            .try_fold(self.rules.get(*first)?, |rule, index| {
+                rule.rules.get(*index)
+            })
+    }
+
+    fn applies_to_extension(&self, extension: Option<&str>) -> bool {
+        extension.is_some_and(|extension| self.extensions.iter().any(|known| known == extension))
+    }
+
+    fn applies_to_interpreter(&self, interpreter: Option<&str>) -> bool {
+        interpreter
+            .is_some_and(|interpreter| self.shebangs.iter().any(|known| known == interpreter))
+    }
+}
+
+/// The languages loaded from the syntax directory, which take precedence
+/// over the built-in ones.
+#[derive(Default)]
+pub struct Syntaxes {
+    definitions: Vec<Rc<SyntaxDefinition>>,
+}
+
+impl Syntaxes {
+    /// Loads every syntax file, returning the problems of the files that
+    /// couldn't be used next to the languages of the others.
+    pub fn load() -> (Self, Vec<String>) {
+        let (definitions, errors) = user::load_definitions();
+        let syntaxes = Self {
+            definitions: definitions.into_iter().map(Rc::new).collect(),
+        };
+
+        (syntaxes, errors)
+    }
+
+    /// Finds the language of a buffer by the extension of its file, then by
+    /// the interpreter named on its `#!` line.
+    pub fn definition_for(&self, buffer: &Buffer) -> Option<Rc<SyntaxDefinition>> {
+        let extension = buffer
+            .file_path
+            .as_deref()
+            .and_then(Path::extension)
+            .map(|extension| extension.to_string_lossy().to_string());
+        let interpreter = buffer.line_text(0).as_deref().and_then(shebang_interpreter);
+
+        self.definitions
+            .iter()
+            .find(|definition| definition.applies_to_extension(extension.as_deref()))
+            .cloned()
+            .or_else(|| languages::definition(&buffer.file_type()).map(Rc::new))
+            .or_else(|| {
+                self.definitions
+                    .iter()
+                    .find(|definition| definition.applies_to_interpreter(interpreter.as_deref()))
+                    .cloned()
+            })
+            .or_else(|| {
+                languages::interpreter_file_type(interpreter.as_deref()?)
+                    .and_then(languages::definition)
+                    .map(Rc::new)
+            })
+    }
+}
+
+/// Name of the program a `#!` line runs, looking past `env` and its options.
+fn shebang_interpreter(first_line: &str) -> Option<String> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let program = Path::new(words.next()?).file_name()?.to_string_lossy();
+
+    if program == "env" {
+        return words
+            .find(|word| !word.starts_with('-') && !word.contains('='))
+            .map(str::to_string);
+    }
+    Some(program.to_string())
+}
+
+/// Highlighted part of a line, as a byte range.
+pub struct Token {
+    pub kind: TokenKind,
+    pub bytes: Range<usize>,
+}
+
+/// The multi-line regions a line starts in, from the outermost one, each
+/// as an index into the rules of the region around it.
+type LineState = Vec<usize>;
+
+/// A region open while tokenizing a line.
+struct Context<'a> {
+    rule: &'a SyntaxRule,
+    /// Where the text inside the region starts on this line.
+    content_start: usize,
+    token_index: usize,
+    next_matches: Vec<Option<Range<usize>>>,
+}
+
+/// Splits the lines of a buffer into tokens, remembering the state every
+/// line starts in so only lines after an edit need to be tokenized again.
+#[derive(Default)]
+pub struct Highlighter {
+    definition: Option<Rc<SyntaxDefinition>>,
+    line_states: Vec<LineState>,
+}
+
+impl Highlighter {
+    pub fn new(definition: Option<Rc<SyntaxDefinition>>) -> Self {
+        Self {
+            definition,
+            line_states: Vec::new(),
+        }
+    }
+
+    pub fn language_name(&self) -> Option<&str> {
+        self.definition
+            .as_ref()
+            .map(|definition| definition.name.as_str())
+    }
+
+    /// Forgets the states of the lines after `line_index`, which an edit of
+    /// that line may have changed.
+    pub fn invalidate_from(&mut self, line_index: usize) {
+        self.line_states.truncate(line_index + 1);
+    }
+
+    pub fn highlight_line(&mut self, buffer: &Buffer, line_index: usize) -> Vec<Token> {
+        let Some(definition) = &self.definition else {
+            return Vec::new();
+        };
+
+        if self.line_states.is_empty() {
+            self.line_states.push(LineState::new());
+        }
+        while self.line_states.len() <= line_index {
+            let previous_line = self.line_states.len() - 1;
+            let text = buffer.line_text(previous_line).unwrap_or_default();
+            let (_, state) = tokenize(definition, &text, &self.line_states[previous_line]);
+            self.line_states.push(state);
+        }
+
+        let text = buffer.line_text(line_index).unwrap_or_default();
+        let (tokens, _) = tokenize(definition, &text, &self.line_states[line_index]);
+        tokens
+    }
+}
+
+/// Tokenizes a line starting in `state`, returning the tokens and the state
+/// the next line starts in. The token of a region comes before the tokens
+/// nested in it.
+fn tokenize(
+    definition: &SyntaxDefinition,
+    line: &str,
+    state: &LineState,
+) -> (Vec<Token>, LineState) {
+    let mut tokens = Vec::new();
+    let mut contexts: Vec<Context> = Vec::new();
+    let mut path = Vec::new();
+
+    for rule_index in state {
+        path.push(*rule_index);
+        let Some(rule) = definition.rule_at(&path) else {
+            break;
+        };
+        contexts.push(Context {
+            rule,
+            content_start: 0,
+            token_index: tokens.len(),
+            next_matches: vec![None; rule.rules.len()],
+        });
+        tokens.push(Token {
+            kind: rule.kind,
+            bytes: 0..line.len(),
+        });
+    }
+    path.truncate(contexts.len());
+
+    let mut top_level_matches = vec![None; definition.rules.len()];
+    let mut position = 0;
+    while position <= line.len() {
+        let (rules, next_matches, region_end) = match contexts.last_mut() {
+            Some(context) => (
+                &context.rule.rules,
+                &mut context.next_matches,
+                find_region_end(context.rule, line, context.content_start, position),
+            ),
+            None => (&definition.rules, &mut top_level_matches, None),
+        };
+
+        let mut best: Option<(usize, Range<usize>)> = None;
+        for (rule_index, rule) in rules.iter().enumerate() {
+            let next_match = &mut next_matches[rule_index];
+            if next_match
+                .as_ref()
+                .is_none_or(|matched| matched.start < position)
+            {
+                *next_match = find_rule_match(rule, line, position);
+            }
+
+            if let Some(matched) = next_match {
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, best_match)| matched.start < best_match.start)
+                {
+                    best = Some((rule_index, matched.clone()));
+                }
+            }
+        }
+
+        // The end of a region wins over the rules inside it starting there.
+        if let Some(end) = region_end.filter(|end| {
+            best.as_ref()
+                .is_none_or(|(_, matched)| end.start <= matched.start)
+        }) {
+            if let Some(context) = contexts.pop() {
+                tokens[context.token_index].bytes.end = end.end;
+            }
+            path.pop();
+            position = end.end;
+            continue;
+        }
+
+        let Some((rule_index, matched)) = best else {
+            break;
+        };
+        let rule = &rules[rule_index];
+
+        if rule.end.is_none() {
+            position = matched.end;
+            tokens.push(Token {
+                kind: rule.kind,
+                bytes: matched,
+            });
+            continue;
+        }
+
+        contexts.push(Context {
+            rule,
+            content_start: matched.end,
+            token_index: tokens.len(),
+            next_matches: vec![None; rule.rules.len()],
+        });
+        path.push(rule_index);
+        tokens.push(Token {
+            kind: rule.kind,
+            bytes: matched.start..line.len(),
+        });
+        position = matched.end;
+    }
+
+    // Regions that end with their line close every region nested in them.
+    let continued_regions = contexts
+        .iter()
+        .take_while(|context| context.rule.multiline)
+        .count();
+    path.truncate(continued_regions);
+    (tokens, path)
+}
+
+/// Range colored by a rule matching at or after `position`, which is its
+/// first capture group when it has one.
+fn find_rule_match(rule: &SyntaxRule, line: &str, position: usize) -> Option<Range<usize>> {
+    let mut search_from = position;
+
+    while search_from <= line.len() {
+        let captures = rule.start.captures_at(line, search_from)?;
+        let matched = captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map(|matched| matched.range())?;
+
+        if !matched.is_empty() && matched.start >= position {
+            return Some(matched);
+        }
+        search_from = next_char_boundary(line, captures.get(0)?.start());
+    }
+
+    None
+}
+
+/// Next end at or after `position` of the region of `rule` whose text starts
+/// at `content_start`, skipping ends preceded by an odd amount of escape
+/// chars.
+fn find_region_end(
+    rule: &SyntaxRule,
+    line: &str,
+    content_start: usize,
+    position: usize,
+) -> Option<Range<usize>> {
+    let end = rule.end.as_ref()?;
+    let mut search_from = position;
+
+    while search_from <= line.len() {
+        let matched = end.find_at(line, search_from)?;
+        let escape_count = rule.escape.map_or(0, |escape| {
+            line[content_start..matched.start()]
+                .chars()
+                .rev()
+                .take_while(|c| *c == escape)
+                .count()
+        });
+
+        if escape_count.is_multiple_of(2) {
+            return Some(matched.range());
+        }
+        search_from = next_char_boundary(line, matched.start());
+    }
+
+    None
+}
+
+fn next_char_boundary(line: &str, index: usize) -> usize {
+    line[index..]
+        .chars()
+        .next()
+        .map_or(index + 1, |c| index + c.len_utf8())
+}
@@ -0,0 +1,265 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use regex::Regex;
+
+use super::{SyntaxDefinition, SyntaxRule, TokenKind};
+
+/// Directory holding the syntax files, next to `config.json`.
+const SYNTAX_DIRECTORY: &str = "syntax";
+const TOKEN_KINDS: &str = "comment, string, number, constant, keyword, type, function, macro, \
+                           key, heading, emphasis, code, link";
+
+/// A language defined in a JSON syntax file. Its comments and strings are
+/// matched before its rules, and its rules before its keywords.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SyntaxFile {
+    name: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    shebangs: Vec<String>,
+    line_comment: Option<String>,
+    block_comment: Option<(String, String)>,
+    #[serde(default)]
+    strings: Vec<StringDelimiter>,
+    /// Words to highlight, by the name of their token kind.
+    #[serde(default)]
+    keywords: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+}
+
+/// A string between two literal delimiters, which are the same unless
+/// `end` is given.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StringDelimiter {
+    delimiter: String,
+    end: Option<String>,
+    escape: Option<char>,
+    #[serde(default)]
+    multiline: bool,
+}
+
+/// A rule with either a `pattern`, or a `start` and `end` for a region that
+/// may have rules of its own.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    kind: String,
+    pattern: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    escape: Option<char>,
+    #[serde(default)]
+    multiline: bool,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+}
+
+/// Loads the definitions of every `.json` file in the syntax directory, in
+/// the order of their names, skipping the files that have problems and
+/// describing those instead.
+pub fn load_definitions() -> (Vec<SyntaxDefinition>, Vec<String>) {
+    let mut definitions = Vec::new();
+    let mut errors = Vec::new();
+
+    let entries = match fs::read_dir(SYNTAX_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return (definitions, errors);
+        }
+        Err(err) => {
+            errors.push(format!("{SYNTAX_DIRECTORY}: {err}"));
+            return (definitions, errors);
+        }
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match load_definition(&path) {
+            Ok(definition) => definitions.push(definition),
+            Err(err) => errors.push(format!("{}: {err}", path.display())),
+        }
+    }
+
+    (definitions, errors)
+}
+
+fn load_definition(path: &Path) -> Result<SyntaxDefinition, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("Couldn't read file: {err}"))?;
+    let file: SyntaxFile = serde_json::from_str(&contents).map_err(|err| err.to_string())?;
+    build_definition(file)
+}
+
+fn build_definition(file: SyntaxFile) -> Result<SyntaxDefinition, String> {
+    if file.name.trim().is_empty() {
+        return Err("name: can't be empty".to_string());
+    }
+    if file.extensions.is_empty() && file.shebangs.is_empty() {
+        return Err("no `extensions` or `shebangs` to choose the files it's used for".to_string());
+    }
+
+    let mut rules = Vec::new();
+
+    if let Some((start, end)) = &file.block_comment {
+        rules.push(literal_region(TokenKind::Comment, start, end, None, true)?);
+    }
+    if let Some(start) = &file.line_comment {
+        if start.is_empty() {
+            return Err("line_comment: can't be empty".to_string());
+        }
+        rules.push(SyntaxRule {
+            kind: TokenKind::Comment,
+            start: compile(&format!("{}.*", regex::escape(start)), "line_comment")?,
+            end: None,
+            escape: None,
+            multiline: false,
+            rules: Vec::new(),
+        });
+    }
+    for (index, string) in file.strings.iter().enumerate() {
+        let end = string.end.as_ref().unwrap_or(&string.delimiter);
+        let rule = literal_region(
+            TokenKind::String,
+            &string.delimiter,
+            end,
+            string.escape,
+            string.multiline,
+        )
+        .map_err(|err| format!("strings[{index}]: {err}"))?;
+        rules.push(rule);
+    }
+    for (index, rule) in file.rules.iter().enumerate() {
+        rules.push(build_rule(rule, &format!("rules[{index}]"))?);
+    }
+    for (kind_name, words) in &file.keywords {
+        let context = format!("keywords.{kind_name}");
+        let kind = parse_kind(kind_name, &context)?;
+        if words.iter().any(String::is_empty) {
+            return Err(format!("{context}: keywords can't be empty"));
+        }
+        if words.is_empty() {
+            continue;
+        }
+
+        let words: Vec<String> = words.iter().map(|word| regex::escape(word)).collect();
+        rules.push(SyntaxRule {
+            kind,
+            start: compile(&format!(r"\b(?:{})\b", words.join("|")), &context)?,
+            end: None,
+            escape: None,
+            multiline: false,
+            rules: Vec::new(),
+        });
+    }
+
+    Ok(SyntaxDefinition {
+        name: file.name,
+        extensions: file
+            .extensions
+            .into_iter()
+            .map(|extension| extension.trim_start_matches('.').to_string())
+            .collect(),
+        shebangs: file.shebangs,
+        rules,
+    })
+}
+
+/// A comment or string region between two delimiters taken literally.
+fn literal_region(
+    kind: TokenKind,
+    start: &str,
+    end: &str,
+    escape: Option<char>,
+    multiline: bool,
+) -> Result<SyntaxRule, String> {
+    if start.is_empty() || end.is_empty() {
+        return Err("delimiters can't be empty".to_string());
+    }
+
+    Ok(SyntaxRule {
+        kind,
+        start: compile(&regex::escape(start), "start")?,
+        end: Some(compile(&regex::escape(end), "end")?),
+        escape,
+        multiline,
+        rules: Vec::new(),
+    })
+}
+
+/// Checks a rule and the rules nested in it, naming the rule by `context`,
+/// like `rules[2].rules[0]`, in the errors.
+fn build_rule(rule: &RuleFile, context: &str) -> Result<SyntaxRule, String> {
+    let kind = parse_kind(&rule.kind, &format!("{context}.kind"))?;
+
+    match (&rule.pattern, &rule.start, &rule.end) {
+        (Some(pattern), None, None) => {
+            if rule.escape.is_some() || rule.multiline || !rule.rules.is_empty() {
+                return Err(format!(
+                    "{context}: `escape`, `multiline` and `rules` need a `start` and `end` \
+                     instead of a `pattern`"
+                ));
+            }
+
+            Ok(SyntaxRule {
+                kind,
+                start: compile(pattern, &format!("{context}.pattern"))?,
+                end: None,
+                escape: None,
+                multiline: false,
+                rules: Vec::new(),
+            })
+        }
+        (None, Some(start), Some(end)) => {
+            let rules = rule
+                .rules
+                .iter()
+                .enumerate()
+                .map(|(index, nested)| build_rule(nested, &format!("{context}.rules[{index}]")))
+                .collect::<Result<_, _>>()?;
+
+            Ok(SyntaxRule {
+                kind,
+                start: compile(start, &format!("{context}.start"))?,
+                end: Some(compile(end, &format!("{context}.end"))?),
+                escape: rule.escape,
+                multiline: rule.multiline,
+                rules,
+            })
+        }
+        (Some(_), _, _) => Err(format!(
+            "{context}: has a `pattern`, so it can't have a `start` or `end`"
+        )),
+        (None, Some(_), None) => Err(format!("{context}: has a `start` but no `end`")),
+        (None, None, Some(_)) => Err(format!("{context}: has an `end` but no `start`")),
+        (None, None, None) => Err(format!(
+            "{context}: needs a `pattern`, or a `start` and `end`"
+        )),
+    }
+}
+
+fn parse_kind(name: &str, context: &str) -> Result<TokenKind, String> {
+    TokenKind::from_name(name)
+        .ok_or_else(|| format!("{context}: unknown kind `{name}`, expected one of {TOKEN_KINDS}"))
+}
+
+/// Compiles a pattern, keeping only the last line of the regex error, which
+/// says what's wrong without drawing the pattern again.
+fn compile(pattern: &str, context: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|err| {
+        let err = err.to_string();
+        let reason = err.lines().last().unwrap_or_default();
+        let reason = reason.strip_prefix("error: ").unwrap_or(reason);
+        format!("{context}: invalid pattern `{pattern}`: {reason}")
+    })
+}
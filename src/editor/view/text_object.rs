@@ -0,0 +1,733 @@
+use std::{ops::Range, sync::LazyLock};
+
+use regex::Regex;
+
+use super::{
+    super::terminal::Position,
+    buffer::Buffer,
+    motion::{self, GraphemeClass},
+};
+
+/// A piece of text around the caret, like a word or what's inside
+/// brackets, for an operator to act on or visual mode to select.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TextObject {
+    pub kind: ObjectKind,
+    /// Whether it takes the white space after it, or the quotes, brackets
+    /// or tags around it, instead of only what's inside.
+    pub around: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+    Word {
+        big: bool,
+    },
+    Sentence,
+    Paragraph,
+    /// Text between two of the quote on a line.
+    Quote(char),
+    Bracket {
+        open: char,
+        close: char,
+    },
+    /// An XML or HTML element.
+    Tag,
+}
+
+/// Where a text object lies.
+pub struct ObjectRange {
+    pub start: Position,
+    /// The last grapheme, or `None` when there is nothing inside the object,
+    /// like in `()`.
+    pub end: Option<Position>,
+    pub linewise: bool,
+}
+
+impl ObjectRange {
+    fn characters(start: Position, end: Position) -> Self {
+        let end = (key(start) <= key(end)).then_some(end);
+        Self {
+            start,
+            end,
+            linewise: false,
+        }
+    }
+
+    /// Whether the object takes more than the text from `from` to `to`,
+    /// and all of it.
+    fn encloses(&self, buffer: &Buffer, from: Position, to: Position) -> bool {
+        let Some(end) = self.end else {
+            return false;
+        };
+        let (start, end) = if self.linewise {
+            (
+                Position {
+                    x: 0,
+                    y: self.start.y,
+                },
+                Position {
+                    x: buffer.line_length(end.y),
+                    y: end.y,
+                },
+            )
+        } else {
+            (self.start, end)
+        };
+        key(start) <= key(from) && key(end) >= key(to) && (start, end) != (from, to)
+    }
+}
+
+/// Run of items that are all blank or all not, like a word or the white
+/// space after it.
+struct Segment {
+    start: usize,
+    end: usize,
+    blank: bool,
+}
+
+/// Finds the text object at a location, taking `count` words, sentences or
+/// paragraphs, or the `count`th brackets or tags out. When `from` and `to`
+/// differ they are the ends of a visual selection for the object to grow:
+/// words, sentences and paragraphs are added after it, and quotes,
+/// brackets and tags are taken further out until they hold all of it.
+pub fn find(
+    buffer: &Buffer,
+    object: TextObject,
+    from: Position,
+    to: Position,
+    count: usize,
+) -> Option<ObjectRange> {
+    let growing = from != to;
+    let count = count.max(1);
+
+    match object.kind {
+        ObjectKind::Word { .. } | ObjectKind::Sentence | ObjectKind::Paragraph => {
+            let at = if growing {
+                next_line_grapheme(buffer, to)?
+            } else {
+                from
+            };
+            let range = match object.kind {
+                ObjectKind::Word { big } => word(buffer, at, count, big, object.around),
+                ObjectKind::Sentence => sentence(buffer, at, count, object.around),
+                _ => paragraph(buffer, at.y, count, object.around),
+            }?;
+            if growing {
+                Some(ObjectRange {
+                    start: from,
+                    ..range
+                })
+            } else {
+                Some(range)
+            }
+        }
+        ObjectKind::Quote(quote) => {
+            let range = quoted(buffer, from, quote, object.around)?;
+            if !growing || range.encloses(buffer, from, to) {
+                return Some(range);
+            }
+            // Selecting inside the quotes again takes the quotes too.
+            let inner = quoted(buffer, from, quote, false)?;
+            let open = previous(buffer, inner.start)?;
+            let close = match inner.end {
+                Some(end) => next(buffer, end)?,
+                None => inner.start,
+            };
+            let range = ObjectRange::characters(open, close);
+            range.encloses(buffer, from, to).then_some(range)
+        }
+        ObjectKind::Bracket { open, close } => {
+            let mut level = count;
+            loop {
+                let range = bracket(buffer, from, open, close, level, object.around)?;
+                if !growing || range.encloses(buffer, from, to) {
+                    return Some(range);
+                }
+                level += 1;
+            }
+        }
+        ObjectKind::Tag => {
+            // Tags are looked for in the lines around the caret first, going
+            // further out only while the element isn't found.
+            let mut reach = TAG_SCAN_LINES;
+            loop {
+                let lines = from.y.saturating_sub(reach)
+                    ..to.y
+                        .saturating_add(reach)
+                        .saturating_add(1)
+                        .min(buffer.line_count());
+                let whole_buffer = lines.start == 0 && lines.end == buffer.line_count();
+                let range = enclosing_tags(buffer, lines, from, to)
+                    .iter()
+                    .skip(count - 1)
+                    .map(|pair| pair.range(buffer, object.around))
+                    .find(|range| !growing || range.encloses(buffer, from, to));
+                if range.is_some() || whole_buffer {
+                    return range;
+                }
+                reach = reach.saturating_mul(2);
+            }
+        }
+    }
+}
+
+/// Orders locations by line, then by grapheme.
+fn key(at: Position) -> (usize, usize) {
+    (at.y, at.x)
+}
+
+/// The location after another, the end of every line being a location of
+/// its own for its line break.
+fn next(buffer: &Buffer, at: Position) -> Option<Position> {
+    if at.x < buffer.line_length(at.y) {
+        Some(Position {
+            x: at.x + 1,
+            y: at.y,
+        })
+    } else if at.y + 1 < buffer.line_count() {
+        Some(Position { x: 0, y: at.y + 1 })
+    } else {
+        None
+    }
+}
+
+fn previous(buffer: &Buffer, at: Position) -> Option<Position> {
+    if at.x > 0 {
+        Some(Position {
+            x: at.x - 1,
+            y: at.y,
+        })
+    } else if at.y > 0 {
+        Some(Position {
+            x: buffer.line_length(at.y - 1),
+            y: at.y - 1,
+        })
+    } else {
+        None
+    }
+}
+
+/// The grapheme after a location, skipping line breaks.
+fn next_line_grapheme(buffer: &Buffer, at: Position) -> Option<Position> {
+    let next = next(buffer, at)?;
+    if next.x < buffer.line_length(next.y) {
+        Some(next)
+    } else {
+        self::next(buffer, next)
+    }
+}
+
+fn is_blank_line(buffer: &Buffer, line_index: usize) -> bool {
+    buffer
+        .line_text(line_index)
+        .is_none_or(|text| text.trim().is_empty())
+}
+
+/// Splits items into runs of blank and non-blank ones, where `same_run`
+/// tells whether an item goes on with the run before it.
+fn segments(blanks: &[bool], same_run: impl Fn(usize) -> bool) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for (index, blank) in blanks.iter().enumerate() {
+        match segments.last_mut() {
+            Some(segment) if segment.blank == *blank && same_run(index) => segment.end = index,
+            _ => segments.push(Segment {
+                start: index,
+                end: index,
+                blank: *blank,
+            }),
+        }
+    }
+    segments
+}
+
+/// Segments taken by an object starting in the `index`th one. Inner objects
+/// count blank segments as objects of their own. Around objects take the
+/// blank segment after every non-blank one, or the one before the first
+/// when there is none after the last, and start on a blank segment with
+/// the non-blank one after it.
+fn select_segments(
+    segments: &[Segment],
+    index: usize,
+    count: usize,
+    around: bool,
+) -> (usize, usize) {
+    let last = segments.len() - 1;
+    if !around {
+        return (index, std::cmp::min(index + count - 1, last));
+    }
+
+    let starts_blank = segments[index].blank;
+    let mut end = index;
+    for taken in 0..count {
+        if taken > 0 {
+            if end == last {
+                break;
+            }
+            end += 1;
+        }
+        let takes_next = if starts_blank {
+            segments[end].blank
+        } else {
+            !segments[end].blank
+        };
+        if takes_next && end < last && segments[end + 1].blank != segments[end].blank {
+            end += 1;
+        }
+    }
+
+    let start = if !starts_blank && !segments[end].blank && index > 0 && segments[index - 1].blank {
+        index - 1
+    } else {
+        index
+    };
+    (start, end)
+}
+
+/// Words of the caret line, where white space is a word of its own for the
+/// inner object.
+fn word(
+    buffer: &Buffer,
+    at: Position,
+    count: usize,
+    big: bool,
+    around: bool,
+) -> Option<ObjectRange> {
+    let line = buffer.line(at.y)?;
+    let line_length = line.grapheme_count();
+    if line_length == 0 {
+        return None;
+    }
+
+    let classes: Vec<GraphemeClass> = (0..line_length)
+        .map(|x| motion::class(line.grapheme(x), big))
+        .collect();
+    let blanks: Vec<bool> = classes
+        .iter()
+        .map(|class| *class == GraphemeClass::Blank)
+        .collect();
+    let segments = segments(&blanks, |x| classes[x] == classes[x - 1]);
+
+    let x = std::cmp::min(at.x, line_length - 1);
+    let index = segments.iter().position(|segment| segment.end >= x)?;
+    let (first, last) = select_segments(&segments, index, count, around);
+
+    Some(ObjectRange::characters(
+        Position {
+            x: segments[first].start,
+            y: at.y,
+        },
+        Position {
+            x: segments[last].end,
+            y: at.y,
+        },
+    ))
+}
+
+/// Sentences of the paragraph around the caret, ending at `.`, `!` or `?`
+/// followed by white space, or at the end of the paragraph.
+fn sentence(buffer: &Buffer, at: Position, count: usize, around: bool) -> Option<ObjectRange> {
+    if is_blank_line(buffer, at.y) {
+        return None;
+    }
+    let first_line = (0..at.y)
+        .rev()
+        .take_while(|line_index| !is_blank_line(buffer, *line_index))
+        .last()
+        .unwrap_or(at.y);
+    let last_line = (at.y + 1..buffer.line_count())
+        .take_while(|line_index| !is_blank_line(buffer, *line_index))
+        .last()
+        .unwrap_or(at.y);
+
+    // Line breaks inside the paragraph are white space between sentences.
+    let mut graphemes: Vec<(Position, String)> = Vec::new();
+    for line_index in first_line..=last_line {
+        let line = buffer.line(line_index)?;
+        let line_length = line.grapheme_count();
+        for x in 0..line_length {
+            graphemes.push((Position { x, y: line_index }, line.grapheme(x).to_string()));
+        }
+        if line_index < last_line {
+            graphemes.push((
+                Position {
+                    x: line_length,
+                    y: line_index,
+                },
+                String::from(" "),
+            ));
+        }
+    }
+
+    let is_blank = |grapheme: &str| grapheme.chars().all(char::is_whitespace);
+    let blanks: Vec<bool> = graphemes.iter().map(|(_, g)| is_blank(g)).collect();
+    let ends_sentence = |index: usize| {
+        let mut before = index;
+        while before > 0 && [")", "]", "\"", "'"].contains(&graphemes[before].1.as_str()) {
+            before -= 1;
+        }
+        [".", "!", "?"].contains(&graphemes[before].1.as_str())
+    };
+    // Only white space after the end of a sentence separates it from the
+    // next, any other being part of the sentence.
+    let mut segments = Vec::new();
+    let mut index = 0;
+    while index < blanks.len() {
+        let start = index;
+        if blanks[index] {
+            while index < blanks.len() && blanks[index] {
+                index += 1;
+            }
+        } else {
+            loop {
+                while index < blanks.len() && !blanks[index] {
+                    index += 1;
+                }
+                let blank_start = index;
+                if index == blanks.len() || ends_sentence(index - 1) {
+                    break;
+                }
+                while index < blanks.len() && blanks[index] {
+                    index += 1;
+                }
+                if index == blanks.len() {
+                    index = blank_start;
+                    break;
+                }
+            }
+        }
+        segments.push(Segment {
+            start,
+            end: index - 1,
+            blank: blanks[start],
+        });
+    }
+
+    let caret_index = graphemes
+        .iter()
+        .rposition(|(position, _)| key(*position) <= key(at))?;
+    let index = segments
+        .iter()
+        .position(|segment| segment.end >= caret_index)?;
+    let (first, last) = select_segments(&segments, index, count, around);
+
+    Some(ObjectRange::characters(
+        graphemes[segments[first].start].0,
+        graphemes[segments[last].end].0,
+    ))
+}
+
+/// Lines of the paragraph around a line, where a run of blank lines is a
+/// paragraph of its own for the inner object.
+fn paragraph(
+    buffer: &Buffer,
+    line_index: usize,
+    count: usize,
+    around: bool,
+) -> Option<ObjectRange> {
+    let line_count = buffer.line_count();
+    if line_index >= line_count {
+        return None;
+    }
+
+    // The run of blank or non-blank lines holding a line, found by walking
+    // to the lines around it.
+    let segment_at = |line_index: usize| {
+        let blank = is_blank_line(buffer, line_index);
+        let same_run = |index: &usize| is_blank_line(buffer, *index) == blank;
+        Segment {
+            start: (0..line_index)
+                .rev()
+                .take_while(same_run)
+                .last()
+                .unwrap_or(line_index),
+            end: (line_index + 1..line_count)
+                .take_while(same_run)
+                .last()
+                .unwrap_or(line_index),
+            blank,
+        }
+    };
+
+    // Only the run before and the runs after that `count` can take are
+    // read, which is two for every paragraph and one to tell what follows.
+    let current = segment_at(line_index);
+    let mut segments = Vec::new();
+    if current.start > 0 {
+        segments.push(segment_at(current.start - 1));
+    }
+    let index = segments.len();
+    segments.push(current);
+    while segments.len() <= index + count.saturating_mul(2) {
+        match segments.last() {
+            Some(last) if last.end + 1 < line_count => segments.push(segment_at(last.end + 1)),
+            _ => break,
+        }
+    }
+    let (first, last) = select_segments(&segments, index, count, around);
+
+    Some(ObjectRange {
+        start: Position {
+            x: 0,
+            y: segments[first].start,
+        },
+        end: Some(Position {
+            x: 0,
+            y: segments[last].end,
+        }),
+        linewise: true,
+    })
+}
+
+/// Text between quotes on the caret line, pairing the quotes from the start
+/// of the line when the caret is on one and taking the nearest ones around
+/// it otherwise. Quotes escaped with `\` don't count. The around object
+/// takes the white space after the closing quote, or before the opening
+/// one when there is none after.
+fn quoted(buffer: &Buffer, at: Position, quote: char, around: bool) -> Option<ObjectRange> {
+    let line = buffer.line(at.y)?;
+    let line_length = line.grapheme_count();
+    let quote = quote.to_string();
+    let mut quotes = Vec::new();
+    let mut escaped = false;
+    for x in 0..line_length {
+        let grapheme = line.grapheme(x);
+        if grapheme == quote && !escaped {
+            quotes.push(x);
+        }
+        escaped = grapheme == "\\" && !escaped;
+    }
+
+    let x = at.x;
+    let (open, close) = if let Some(index) = quotes.iter().position(|q| *q == x) {
+        if index % 2 == 0 {
+            (x, *quotes.get(index + 1)?)
+        } else {
+            (quotes[index - 1], x)
+        }
+    } else {
+        match quotes.iter().rposition(|q| *q < x) {
+            Some(before) => (quotes[before], *quotes.get(before + 1)?),
+            None => (*quotes.first()?, *quotes.get(1)?),
+        }
+    };
+
+    let position = |x: usize| Position { x, y: at.y };
+    if !around {
+        return Some(ObjectRange::characters(
+            position(open + 1),
+            position(close - 1),
+        ));
+    }
+
+    let is_blank = |x: usize| line.grapheme(x).chars().all(char::is_whitespace);
+    let mut start = open;
+    let mut end = close;
+    while end + 1 < line_length && is_blank(end + 1) {
+        end += 1;
+    }
+    if end == close {
+        while start > 0 && is_blank(start - 1) {
+            start -= 1;
+        }
+    }
+    Some(ObjectRange::characters(position(start), position(end)))
+}
+
+/// Text between the `level`th unmatched opening bracket before the caret,
+/// or at it, and its closing bracket. When the opening bracket ends its
+/// line and the closing one starts its own, the inner object is the lines
+/// between them.
+fn bracket(
+    buffer: &Buffer,
+    at: Position,
+    open: char,
+    close: char,
+    level: usize,
+    around: bool,
+) -> Option<ObjectRange> {
+    let opener = find_opener(buffer, at, open, close, level)?;
+    let closer = find_closer(buffer, opener, open, close)?;
+    if around {
+        return Some(ObjectRange::characters(opener, closer));
+    }
+
+    let opener_ends_line = opener.x + 1 >= buffer.line_length(opener.y);
+    let closer_starts_line = closer.y > opener.y
+        && buffer.line(closer.y).is_some_and(|line| {
+            (0..closer.x).all(|x| line.grapheme(x).chars().all(char::is_whitespace))
+        });
+
+    if opener_ends_line && closer_starts_line {
+        return Some(ObjectRange {
+            start: Position {
+                x: 0,
+                y: opener.y + 1,
+            },
+            end: (opener.y + 1 < closer.y).then_some(Position {
+                x: 0,
+                y: closer.y - 1,
+            }),
+            linewise: true,
+        });
+    }
+
+    let start = next(buffer, opener)?;
+    let start = if opener_ends_line {
+        next(buffer, start)?
+    } else {
+        start
+    };
+    let end = if closer_starts_line {
+        let line_break = previous(buffer, Position { x: 0, y: closer.y })?;
+        previous(buffer, line_break)?
+    } else {
+        previous(buffer, closer)?
+    };
+    Some(ObjectRange::characters(start, end))
+}
+
+fn find_opener(
+    buffer: &Buffer,
+    at: Position,
+    open: char,
+    close: char,
+    level: usize,
+) -> Option<Position> {
+    let (open, close) = (open.to_string(), close.to_string());
+    let mut depth = 0;
+    let mut level = level;
+
+    for line_index in (0..=at.y).rev() {
+        let line = buffer.line(line_index)?;
+        let end = if line_index == at.y {
+            std::cmp::min(at.x + 1, line.grapheme_count())
+        } else {
+            line.grapheme_count()
+        };
+        for x in (0..end).rev() {
+            let grapheme = line.grapheme(x);
+            // A closing bracket at the caret belongs to the pair it closes.
+            if grapheme == close && (line_index, x) != (at.y, at.x) {
+                depth += 1;
+            } else if grapheme == open && depth > 0 {
+                depth -= 1;
+            } else if grapheme == open {
+                level -= 1;
+                if level == 0 {
+                    return Some(Position { x, y: line_index });
+                }
+            }
+        }
+    }
+    None
+}
+
+fn find_closer(buffer: &Buffer, opener: Position, open: char, close: char) -> Option<Position> {
+    let (open, close) = (open.to_string(), close.to_string());
+    let mut depth = 0;
+
+    for line_index in opener.y..buffer.line_count() {
+        let line = buffer.line(line_index)?;
+        let start = if line_index == opener.y {
+            opener.x + 1
+        } else {
+            0
+        };
+        for x in start..line.grapheme_count() {
+            let grapheme = line.grapheme(x);
+            if grapheme == open {
+                depth += 1;
+            } else if grapheme == close && depth > 0 {
+                depth -= 1;
+            } else if grapheme == close {
+                return Some(Position { x, y: line_index });
+            }
+        }
+    }
+    None
+}
+
+/// Lines above and below the caret searched for tags before going further.
+const TAG_SCAN_LINES: usize = 64;
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(/?)([A-Za-z][\w:.-]*)[^<>]*?(/?)>").expect("The tag pattern is valid")
+});
+
+/// An element's opening and closing tags, by the locations of their `<`
+/// and `>`.
+struct TagPair {
+    open: (Position, Position),
+    close: (Position, Position),
+}
+
+impl TagPair {
+    fn range(&self, buffer: &Buffer, around: bool) -> ObjectRange {
+        if around {
+            return ObjectRange::characters(self.open.0, self.close.1);
+        }
+        match (next(buffer, self.open.1), previous(buffer, self.close.0)) {
+            (Some(start), Some(end)) => ObjectRange::characters(start, end),
+            _ => ObjectRange {
+                start: self.close.0,
+                end: None,
+                linewise: false,
+            },
+        }
+    }
+}
+
+/// The elements within `lines` holding the text from `from` to `to`,
+/// innermost first. Tags are matched by name, skipping opening tags that are
+/// never closed, like `<br>`.
+fn enclosing_tags(
+    buffer: &Buffer,
+    lines: Range<usize>,
+    from: Position,
+    to: Position,
+) -> Vec<TagPair> {
+    let mut open_tags: Vec<(String, (Position, Position))> = Vec::new();
+    let mut pairs = Vec::new();
+    for line_index in lines {
+        let (Some(line), Some(text)) = (buffer.line(line_index), buffer.line_text(line_index))
+        else {
+            continue;
+        };
+        for captures in TAG.captures_iter(&text) {
+            let Some(whole) = captures.get(0) else {
+                continue;
+            };
+            let bounds = (
+                Position {
+                    x: line.grapheme_index_at_byte(whole.start()),
+                    y: line_index,
+                },
+                Position {
+                    x: line.grapheme_index_at_byte(whole.end() - 1),
+                    y: line_index,
+                },
+            );
+            let name = captures[2].to_string();
+            if !captures[1].is_empty() {
+                if let Some(index) = open_tags.iter().rposition(|(open, _)| *open == name) {
+                    let open = open_tags[index].1;
+                    open_tags.truncate(index);
+                    pairs.push(TagPair {
+                        open,
+                        close: bounds,
+                    });
+                }
+            } else if captures[3].is_empty() {
+                open_tags.push((name, bounds));
+            }
+        }
+    }
+
+    let mut enclosing: Vec<TagPair> = pairs
+        .into_iter()
+        .filter(|pair| key(pair.open.0) <= key(from) && key(pair.close.1) >= key(to))
+        .collect();
+    enclosing.sort_by_key(|pair| std::cmp::Reverse(key(pair.open.0)));
+    enclosing
+}